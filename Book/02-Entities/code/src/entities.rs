@@ -1,12 +1,76 @@
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+// GUIDE: this is the book's example generator from chapter 2, entities are plain ids that never get reused.
+// The generational `Entity` in Safecs (`safecs::entity::Entity`) is the real one the book builds up to
+// ANCHOR: entity
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Entity(u64);
+// ANCHOR_END: entity
 
+// The derived impl would hash the id in whatever byte order the platform uses, always going little
+// endian means a given hasher gives the same hash for an entity everywhere
+impl std::hash::Hash for Entity {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write(&self.0.to_le_bytes());
+    }
+}
+
+// ANCHOR: generator_struct
 pub(crate) struct EntityGenerator {
     next_id: u64,
-    dead_entities: std::collections::HashSet<Entity>,
+    // Every id below this is dead, `drain_dead` moves it up so those ids don't need to be stored anymore
+    dead_below: u64,
+    dead_entities: EntityStatuses,
 }
+// ANCHOR_END: generator_struct
 
 impl EntityGenerator {
+    // Both of these behave exactly the same, they only differ in how much memory they use and how fast they are
+    pub(crate) fn new_hashset() -> Self {
+        Self {
+            next_id: 0,
+            dead_below: 0,
+            dead_entities: EntityStatuses::HashsetMethod(HashsetMethod(
+                std::collections::HashSet::new(),
+            )),
+        }
+    }
+
+    pub(crate) fn new_bitset() -> Self {
+        Self {
+            next_id: 0,
+            dead_below: 0,
+            dead_entities: EntityStatuses::BitsetMethod(BitsetMethod(Vec::new())),
+        }
+    }
+
+    // Same again but kept in a sorted vec, for tiny worlds a binary search over a handful of ids beats hashing
+    pub(crate) fn new_sorted_vec() -> Self {
+        Self {
+            next_id: 0,
+            dead_below: 0,
+            dead_entities: EntityStatuses::SortedVecMethod(SortedVecMethod(Vec::new())),
+        }
+    }
+
+    // Rebuilds a generator from a save without replaying every spawn and despawn, if one of the dead
+    // entities could never have been spawned by the saved generator it gets handed back as the error
+    pub(crate) fn load(
+        next_id: u64,
+        dead: impl IntoIterator<Item = Entity>,
+    ) -> Result<EntityGenerator, Entity> {
+        let mut generator = EntityGenerator {
+            next_id,
+            ..EntityGenerator::new_hashset()
+        };
+        for entity in dead {
+            if entity.0 >= next_id {
+                return Err(entity);
+            }
+            generator.dead_entities.despawn(entity);
+        }
+        Ok(generator)
+    }
+
+    // ANCHOR: generator_methods
     pub(crate) fn spawn(&mut self) -> Entity {
         let entity = Entity(self.next_id);
         if self.next_id == u64::MAX {
@@ -18,7 +82,7 @@ impl EntityGenerator {
 
     pub(crate) fn despawn(&mut self, entity: Entity) {
         if self.is_alive(entity) {
-            self.dead_entities.insert(entity);
+            self.dead_entities.despawn(entity);
         }
     }
 
@@ -28,6 +92,478 @@ impl EntityGenerator {
         if entity.0 >= self.next_id {
             panic!("Attempted to use an entity in an EntityGenerator that it was not spawned with");
         }
-        self.dead_entities.contains(&entity) == false
+        entity.0 >= self.dead_below && self.dead_entities.is_dead(entity) == false
+    }
+    // ANCHOR_END: generator_methods
+
+    // Same as `is_alive` except an entity this generator never spawned is just treated as not alive,
+    // the panic is great for catching bugs but you might not want to crash over it in a release build
+    pub(crate) fn try_is_alive(&self, entity: Entity) -> bool {
+        entity.0 < self.next_id
+            && entity.0 >= self.dead_below
+            && self.dead_entities.is_dead(entity) == false
+    }
+
+    // Only drains the run of dead entities before the oldest entity that's still alive, not every dead entity!
+    // Those get handed back and are no longer stored, they stay dead since everything below `dead_below` is.
+    // A dead entity that comes after a living one stays stored and isn't handed back until everything before
+    // it has died too, there'd be no way to remember it's dead otherwise
+    pub(crate) fn drain_dead(&mut self) -> impl Iterator<Item = Entity> {
+        let start = self.dead_below;
+        let mut end = start;
+        while end < self.next_id && self.dead_entities.is_dead(Entity(end)) {
+            end += 1;
+        }
+        self.dead_entities.forget(start..end);
+        self.dead_below = end;
+        (start..end).map(Entity)
+    }
+
+    // Every entity ever spawned, dead or alive. Nothing gets reused so this only ever goes up, which is
+    // exactly the memory growth problem the generational entities later on are there to fix
+    pub(crate) fn total_spawned(&self) -> u64 {
+        self.next_id
+    }
+
+    pub(crate) fn alive_count(&self) -> u64 {
+        self.next_id - self.dead_below - self.dead_entities.len()
+    }
+
+    // Releases any spare capacity in the dead entities storage, liveness isn't affected
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.dead_entities.shrink_to_fit();
+    }
+
+    // Every id below next_id has been spawned so we can just walk them all and skip the dead ones
+    pub(crate) fn iter_alive(&self) -> impl Iterator<Item = Entity> + '_ {
+        (self.dead_below..self.next_id)
+            .map(Entity)
+            .filter(move |&entity| self.dead_entities.is_dead(entity) == false)
+    }
+}
+
+// The two ways of storing dead entities from the chapter, option #2 and option #3,
+// plus a sorted vec which is option #2 without the hashing
+#[allow(clippy::enum_variant_names)] // the variants are named after the methods in the chapter
+enum EntityStatuses {
+    HashsetMethod(HashsetMethod),
+    BitsetMethod(BitsetMethod),
+    SortedVecMethod(SortedVecMethod),
+}
+
+impl EntityStatuses {
+    fn despawn(&mut self, entity: Entity) {
+        match self {
+            EntityStatuses::HashsetMethod(method) => method.despawn(entity),
+            EntityStatuses::BitsetMethod(method) => method.despawn(entity),
+            EntityStatuses::SortedVecMethod(method) => method.despawn(entity),
+        }
+    }
+
+    // Stops storing a run of dead entities, ids below `range` must already have been forgotten. The
+    // bitset just clears their bits since it needs a bit for every id up to the last dead one either way
+    fn forget(&mut self, range: std::ops::Range<u64>) {
+        match self {
+            EntityStatuses::HashsetMethod(method) => {
+                for id in range {
+                    method.0.remove(&Entity(id));
+                }
+            }
+            EntityStatuses::BitsetMethod(method) => {
+                for id in range {
+                    method.0[id as usize / 64] &= !(1 << (id % 64));
+                }
+            }
+            // Nothing below `range` is left so it's right at the front
+            EntityStatuses::SortedVecMethod(method) => {
+                method.0.drain(..(range.end - range.start) as usize);
+            }
+        }
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            EntityStatuses::HashsetMethod(method) => method.0.len() as u64,
+            EntityStatuses::BitsetMethod(method) => {
+                method.0.iter().map(|word| word.count_ones() as u64).sum()
+            }
+            EntityStatuses::SortedVecMethod(method) => method.0.len() as u64,
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        match self {
+            EntityStatuses::HashsetMethod(method) => method.0.shrink_to_fit(),
+            EntityStatuses::BitsetMethod(method) => method.0.shrink_to_fit(),
+            EntityStatuses::SortedVecMethod(method) => method.0.shrink_to_fit(),
+        }
+    }
+
+    fn is_dead(&self, entity: Entity) -> bool {
+        match self {
+            EntityStatuses::HashsetMethod(method) => method.is_dead(entity),
+            EntityStatuses::BitsetMethod(method) => method.is_dead(entity),
+            EntityStatuses::SortedVecMethod(method) => method.is_dead(entity),
+        }
+    }
+}
+
+struct HashsetMethod(std::collections::HashSet<Entity>);
+
+impl HashsetMethod {
+    fn despawn(&mut self, entity: Entity) {
+        self.0.insert(entity);
+    }
+
+    fn is_dead(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+// One bit per entity id, a set bit means the entity is dead
+struct BitsetMethod(Vec<u64>);
+
+impl BitsetMethod {
+    fn despawn(&mut self, entity: Entity) {
+        // We only grow the vec when we despawn something past the end of it, if we spawn a
+        // million entities and never despawn any of them we shouldn't be paying for a million bits
+        let word = entity.0 as usize / 64;
+        if self.0.len() < word + 1 {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << (entity.0 % 64);
+    }
+
+    // Anything past the end of the vec hasn't been despawned yet so it can't be dead
+    fn is_dead(&self, entity: Entity) -> bool {
+        match self.0.get(entity.0 as usize / 64) {
+            Some(word) => word & (1 << (entity.0 % 64)) != 0,
+            None => false,
+        }
+    }
+}
+
+// The ids of dead entities kept sorted and without duplicates so we can binary search it
+struct SortedVecMethod(Vec<u64>);
+
+impl SortedVecMethod {
+    fn despawn(&mut self, entity: Entity) {
+        // Err gives us where the id would need to go to keep the vec sorted
+        if let Err(idx) = self.0.binary_search(&entity.0) {
+            self.0.insert(idx, entity.0);
+        }
+    }
+
+    fn is_dead(&self, entity: Entity) -> bool {
+        self.0.binary_search(&entity.0).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One generator for each way of storing dead entities, anything that isn't about one method in particular
+    // should hold for all of them
+    fn every_method() -> [EntityGenerator; 3] {
+        [
+            EntityGenerator::new_hashset(),
+            EntityGenerator::new_bitset(),
+            EntityGenerator::new_sorted_vec(),
+        ]
+    }
+
+    // FNV-1a, std's `DefaultHasher` is allowed to change between releases so it's no good for pinning a hash
+    struct Fnv(u64);
+
+    impl std::hash::Hasher for Fnv {
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn hash_is_little_endian() {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = Fnv(0xcbf29ce484222325);
+        Entity(0x0102030405060708).hash(&mut hasher);
+        assert_eq!(hasher.finish(), 0x0c6d4496e17859d5);
+    }
+
+    #[test]
+    fn counts() {
+        for mut generator in every_method() {
+            let entities: Vec<_> = (0..100).map(|_| generator.spawn()).collect();
+            for &i in [3, 70, 3, 99].iter() {
+                generator.despawn(entities[i]);
+            }
+            assert_eq!(generator.total_spawned(), 100);
+            assert_eq!(generator.alive_count(), 97);
+
+            generator.spawn();
+            assert_eq!(generator.total_spawned(), 101);
+            assert_eq!(generator.alive_count(), 98);
+        }
+    }
+
+    #[test]
+    fn iter_alive() {
+        for mut generator in every_method() {
+            let entities: Vec<_> = (0..5).map(|_| generator.spawn()).collect();
+            generator.despawn(entities[1]);
+            generator.despawn(entities[4]);
+
+            let alive: Vec<_> = generator.iter_alive().collect();
+            assert_eq!(alive, vec![entities[0], entities[2], entities[3]]);
+        }
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut generator = EntityGenerator::new_hashset();
+        let entities: Vec<_> = (0..10).map(|_| generator.spawn()).collect();
+        match &mut generator.dead_entities {
+            EntityStatuses::HashsetMethod(hashset) => hashset.0.reserve(1000),
+            _ => unreachable!(),
+        }
+        generator.despawn(entities[3]);
+
+        generator.shrink_to_fit();
+        match &generator.dead_entities {
+            EntityStatuses::HashsetMethod(hashset) => assert!(hashset.0.capacity() < 1000),
+            _ => unreachable!(),
+        }
+        assert!(generator.is_alive(entities[3]) == false);
+        assert_eq!(generator.iter_alive().count(), 9);
+
+        let mut generator = EntityGenerator::new_bitset();
+        let entities: Vec<_> = (0..10).map(|_| generator.spawn()).collect();
+        match &mut generator.dead_entities {
+            EntityStatuses::BitsetMethod(bitset) => bitset.0.reserve(1000),
+            _ => unreachable!(),
+        }
+        generator.despawn(entities[3]);
+
+        generator.shrink_to_fit();
+        match &generator.dead_entities {
+            // Only one word is needed, the allocator is free to hand back a bit more than that
+            EntityStatuses::BitsetMethod(bitset) => assert!(bitset.0.capacity() < 1000),
+            _ => unreachable!(),
+        }
+        assert!(generator.is_alive(entities[3]) == false);
+        assert_eq!(generator.iter_alive().count(), 9);
+    }
+
+    // Every method is driven through the same spawns/despawns side by side and has to give the same answers,
+    // so when they drift apart the failure points at the exact step
+    #[test]
+    fn methods_equivalent() {
+        let mut generators = every_method();
+        let mut entities = Vec::new();
+        for step in 0..300usize {
+            let spawned: Vec<_> = generators.iter_mut().map(|g| g.spawn()).collect();
+            assert!(
+                spawned.iter().all(|&e| e == spawned[0]),
+                "spawn differs at step {}",
+                step
+            );
+            entities.push(spawned[0]);
+
+            let despawn = match step % 4 {
+                0 => Some(entities[step / 2]),
+                // Sometimes this one was already despawned by the arm above, which has to be a no-op for every method
+                1 => Some(entities[step - 1]),
+                _ => None,
+            };
+            if let Some(entity) = despawn {
+                for generator in generators.iter_mut() {
+                    generator.despawn(entity);
+                }
+            }
+
+            for &e in entities.iter() {
+                let expected = generators[0].is_alive(e);
+                for generator in generators[1..].iter() {
+                    assert_eq!(
+                        generator.is_alive(e),
+                        expected,
+                        "{:?} differs at step {}",
+                        e,
+                        step
+                    );
+                }
+            }
+        }
+        let alive = generators[0].alive_count();
+        assert!(alive < 300);
+        for generator in generators[1..].iter() {
+            assert_eq!(generator.alive_count(), alive);
+        }
+    }
+
+    #[test]
+    fn sorted_vec_stays_sorted() {
+        let mut generator = EntityGenerator::new_sorted_vec();
+        let entities: Vec<_> = (0..10).map(|_| generator.spawn()).collect();
+        for &i in [7, 2, 9, 2, 0, 7].iter() {
+            generator.despawn(entities[i]);
+        }
+
+        match &generator.dead_entities {
+            EntityStatuses::SortedVecMethod(sorted) => assert_eq!(sorted.0, vec![0, 2, 7, 9]),
+            _ => unreachable!(),
+        }
+        let alive: Vec<_> = generator.iter_alive().map(|e| e.0).collect();
+        assert_eq!(alive, vec![1, 3, 4, 5, 6, 8]);
+    }
+
+    #[test]
+    fn load() {
+        let mut generator = EntityGenerator::new_bitset();
+        let entities: Vec<_> = (0..100).map(|_| generator.spawn()).collect();
+        for e in entities.iter().filter(|e| e.0 % 7 < 3) {
+            generator.despawn(*e);
+        }
+
+        let dead = entities
+            .iter()
+            .copied()
+            .filter(|&e| generator.is_alive(e) == false);
+        let mut loaded = EntityGenerator::load(generator.next_id, dead).unwrap();
+        for &e in entities.iter() {
+            assert_eq!(loaded.is_alive(e), generator.is_alive(e));
+        }
+        assert_eq!(loaded.spawn(), generator.spawn());
+
+        assert_eq!(
+            EntityGenerator::load(10, vec![Entity(3), Entity(10)]).err(),
+            Some(Entity(10))
+        );
+    }
+
+    #[test]
+    fn drain_dead() {
+        for mut generator in every_method() {
+            let entities: Vec<_> = (0..100).map(|_| generator.spawn()).collect();
+            for &i in [2, 70, 0, 1, 41].iter() {
+                generator.despawn(entities[i]);
+            }
+
+            // 41 and 70 come after living entities so they're left where they are
+            let drained: Vec<_> = generator.drain_dead().collect();
+            assert_eq!(drained, entities[0..3]);
+            assert_eq!(generator.dead_entities.len(), 2);
+            assert!(generator.dead_entities.is_dead(entities[41]));
+            assert!(generator.is_alive(entities[70]) == false);
+            assert_eq!(generator.drain_dead().count(), 0);
+            // Drained entities stay dead
+            assert!(generator.is_alive(entities[0]) == false);
+            assert!(generator.try_is_alive(entities[2]) == false);
+            generator.despawn(entities[1]);
+            assert_eq!(generator.iter_alive().count(), 95);
+            assert_eq!(generator.alive_count(), 95);
+
+            for &e in entities[3..41].iter() {
+                generator.despawn(e);
+            }
+            let drained: Vec<_> = generator.drain_dead().collect();
+            assert_eq!(drained, entities[3..42]);
+            assert_eq!(generator.dead_entities.len(), 1);
+            assert!(drained.contains(&entities[70]) == false);
+            assert!(generator.is_alive(entities[70]) == false);
+            assert_eq!(generator.alive_count(), 57);
+            assert!(generator.is_alive(entities[42]));
+        }
+    }
+
+    #[test]
+    fn spawn_despawn() {
+        for mut generator in every_method() {
+            let e1 = generator.spawn();
+            let e2 = generator.spawn();
+            assert!(generator.is_alive(e1));
+
+            generator.despawn(e1);
+            generator.despawn(e1);
+            assert!(generator.is_alive(e1) == false);
+            assert!(generator.is_alive(e2));
+        }
+    }
+
+    #[test]
+    fn bitset_many_words() {
+        let mut generator = EntityGenerator::new_bitset();
+        let entities: Vec<_> = (0..200).map(|_| generator.spawn()).collect();
+        for e in entities.iter().filter(|e| e.0 % 3 == 0) {
+            generator.despawn(*e);
+        }
+
+        assert!(generator.dead_entities.is_dead(Entity(63)));
+        assert!(generator.dead_entities.is_dead(Entity(64)) == false);
+        for e in entities.iter() {
+            assert_eq!(generator.is_alive(*e), e.0 % 3 != 0);
+        }
+    }
+
+    #[test]
+    fn bitset_grows_lazily() {
+        let mut generator = EntityGenerator::new_bitset();
+        generator.next_id = 2_000_000;
+        assert!(generator.is_alive(Entity(1_999_999)));
+
+        generator.despawn(Entity(1_000_000));
+        assert!(generator.is_alive(Entity(1_000_000)) == false);
+        assert!(generator.is_alive(Entity(1_000_001)));
+        assert!(generator.is_alive(Entity(1_999_999)));
+        match &generator.dead_entities {
+            EntityStatuses::BitsetMethod(bitset) => assert_eq!(bitset.0.len(), 1_000_000 / 64 + 1),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn try_is_alive() {
+        for mut generator in every_method() {
+            let e1 = generator.spawn();
+            let e2 = generator.spawn();
+            generator.despawn(e1);
+
+            assert!(generator.try_is_alive(e1) == false);
+            assert!(generator.try_is_alive(e2));
+            assert!(generator.try_is_alive(Entity(2)) == false);
+            assert!(generator.try_is_alive(Entity(u64::MAX)) == false);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn strict_is_alive_panics() {
+        let generator = EntityGenerator::new_sorted_vec();
+        generator.is_alive(Entity(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn hashset_other_generator() {
+        let mut gen_1 = EntityGenerator::new_hashset();
+        let gen_2 = EntityGenerator::new_hashset();
+        let e1_1 = gen_1.spawn();
+        gen_2.is_alive(e1_1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn bitset_other_generator() {
+        let mut gen_1 = EntityGenerator::new_bitset();
+        let gen_2 = EntityGenerator::new_bitset();
+        let e1_1 = gen_1.spawn();
+        gen_2.is_alive(e1_1);
     }
 }
@@ -1,3 +1,7 @@
 #![forbid(unsafe_code)]
+// GUIDE: `Entity` is the public face of the crate and doesn't drive the generator at all, it's only exercised by tests for now
+#![allow(dead_code)]
+// I like my `== false` okay
+#![allow(clippy::bool_comparison)]
 mod entities;
 pub use entities::Entity;
@@ -0,0 +1,49 @@
+// `cargo bench --features rayon`, times doubling a million components on one thread and then on all of them.
+// There's no bench harness on stable so this just prints the best of a few runs
+use rayon::prelude::*;
+use safecs::World;
+use std::time::{Duration, Instant};
+
+struct Position(f32, f32, f32);
+
+fn best_of(runs: u32, mut f: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let mut world = World::new();
+    for i in 0..1_000_000 {
+        world.spawn_bundle((Position(i as f32, 0.5, -1.0),));
+    }
+
+    let double = |p: &mut Position| {
+        p.0 *= 2.0;
+        p.1 *= 2.0;
+        p.2 *= 2.0;
+    };
+
+    // `World` isn't `Send` so the iterator is made outside of `install` and moved in
+    let single = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let one_thread = best_of(10, || {
+        let positions = world.par_query_mut::<Position>();
+        single.install(move || positions.for_each(double))
+    });
+    let all_threads = best_of(10, || world.par_query_mut::<Position>().for_each(double));
+
+    println!("1 thread:  {:?}", one_thread);
+    println!(
+        "{} threads: {:?}",
+        rayon::current_num_threads(),
+        all_threads
+    );
+}
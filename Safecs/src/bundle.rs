@@ -0,0 +1,54 @@
+use crate::{Entity, World};
+
+// A group of components that can all be inserted on an entity at once, implemented for tuples
+// of up to 8 components e.g. `world.spawn_bundle((Position(..), Velocity(..)))`
+pub trait Bundle {
+    fn insert_into(self, world: &mut World, entity: Entity);
+}
+
+macro_rules! impl_bundle {
+    ($($name:ident)*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: 'static),*> Bundle for ($($name,)*) {
+            fn insert_into(self, world: &mut World, entity: Entity) {
+                let ($($name,)*) = self;
+                $(world.insert(entity, $name);)*
+            }
+        }
+    };
+}
+
+impl_bundle!(A);
+impl_bundle!(A B);
+impl_bundle!(A B C);
+impl_bundle!(A B C D);
+impl_bundle!(A B C D E);
+impl_bundle!(A B C D E F);
+impl_bundle!(A B C D E F G);
+impl_bundle!(A B C D E F G H);
+
+#[cfg(test)]
+mod tests {
+    use crate::World;
+
+    #[test]
+    fn spawn_bundle() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((1_u32, 'a', true));
+        let e2 = world.spawn_bundle((2_u32,));
+
+        assert_eq!(world.get::<u32>(e1), Some(&1));
+        assert_eq!(world.get::<char>(e1), Some(&'a'));
+        assert_eq!(world.get::<bool>(e1), Some(&true));
+        assert_eq!(world.get::<u32>(e2), Some(&2));
+        assert_eq!(world.get::<char>(e2), None);
+    }
+
+    #[test]
+    fn spawn_bundle_8() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((1_u8, 2_u16, 3_u32, 4_u64, 5_i8, 6_i16, 7_i32, 8_i64));
+        assert_eq!(world.get::<u8>(e1), Some(&1));
+        assert_eq!(world.get::<i64>(e1), Some(&8));
+    }
+}
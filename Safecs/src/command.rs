@@ -0,0 +1,123 @@
+use crate::{Entity, World};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum Command {
+    // The entity has already been reserved so there's nothing left to do for it other than flushing
+    Spawn(Entity),
+    Despawn(Entity),
+}
+
+// Records spawns and despawns to be applied later on with `World::apply`, for when you only have a
+// shared reference to the world (e.g. in the middle of a `query`)
+// GUIDE: entities from `spawn` are reserved in the world straight away and there's no taking a reservation
+// back, so they stay alive (with no components) even if the buffer is dropped without being applied
+#[derive(Default, Debug)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The entity is reserved straight away so it can be referred to before the buffer is applied
+    pub fn spawn(&mut self, world: &World) -> Entity {
+        let entity = world.reserve_entity();
+        self.commands.push(Command::Spawn(entity));
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.commands.push(Command::Despawn(entity));
+    }
+
+    pub(crate) fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, CommandBuffer};
+    use crate::World;
+
+    #[test]
+    fn despawn_while_iterating() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..10_u32).map(|i| world.spawn_bundle((i,))).collect();
+
+        let mut buffer = CommandBuffer::new();
+        for (entity, &i) in world.query::<u32>() {
+            if i % 2 == 0 {
+                buffer.despawn(entity);
+            }
+        }
+        assert_eq!(world.query::<u32>().count(), 10);
+
+        world.apply(buffer);
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(world.is_alive(e), i % 2 == 1);
+            assert_eq!(world.get::<u32>(e).is_some(), i % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn spawn_reserves() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((1_u32,));
+
+        let mut buffer = CommandBuffer::new();
+        let spawned: Vec<_> = world.query::<u32>().map(|_| buffer.spawn(&world)).collect();
+        let e2 = spawned[0];
+        assert!(world.is_alive(e2));
+        buffer.despawn(e1);
+        buffer.despawn(e1);
+        assert_eq!(
+            buffer.commands(),
+            &[
+                Command::Spawn(e2),
+                Command::Despawn(e1),
+                Command::Despawn(e1)
+            ]
+        );
+
+        world.apply(buffer);
+        assert!(world.is_alive(e2));
+        assert!(world.is_alive(e1) == false);
+        world.insert(e2, 2_u32);
+        assert_eq!(world.query::<u32>().collect::<Vec<_>>(), vec![(e2, &2)]);
+    }
+
+    #[test]
+    fn dropped_buffer_spawns() {
+        let mut world = World::new();
+        let mut buffer = CommandBuffer::new();
+        let e1 = buffer.spawn(&world);
+        buffer.despawn(e1);
+        drop(buffer);
+
+        // Only the despawn is lost, the reservation already happened
+        let e2 = world.spawn_bundle((2_u32,));
+        assert!(world.is_alive(e1));
+        assert!(e1 != e2);
+        assert_eq!(world.query::<u32>().collect::<Vec<_>>(), vec![(e2, &2)]);
+    }
+
+    #[test]
+    fn despawn_cleans_up() {
+        let mut world = World::new();
+        let parent = world.spawn_bundle((1_u32,));
+        let child = world.spawn_bundle((2_u32,));
+        world.set_parent(child, parent).unwrap();
+
+        let mut buffer = CommandBuffer::new();
+        buffer.despawn(parent);
+        world.apply(buffer);
+
+        // Goes through `World::despawn` so components and children go with it
+        assert!(world.is_alive(child) == false);
+        assert_eq!(world.query::<u32>().count(), 0);
+        assert!(world.children(parent).is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::entity::{EntityStatus, SpawnError};
+use alloc::vec::Vec;
+
+const INDEX_BITS: u32 = 22;
+const GENERATION_BITS: u32 = 10;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+pub(crate) const MAX_INDEX: u32 = INDEX_MASK;
+pub(crate) const MAX_GENERATION: u32 = (1 << GENERATION_BITS) - 1;
+
+// A 4 byte alternative to `Entity` for when memory is tight, the index lives in the low 22 bits and the
+// generation in the high 10 bits. That caps a world at ~4 million slots and each slot only gets 1023
+// generations (1 to 1023, same as `Entity` generation 0 is never handed out) before it's tombstoned, so slots get retired a *lot* faster than with `Entity`
+// GUIDE: there's no world id in here so compact entities from different generators can't be told apart
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CompactEntity(u32);
+
+impl CompactEntity {
+    fn new(index: u32, generation: u32) -> CompactEntity {
+        debug_assert!(index <= MAX_INDEX && generation <= MAX_GENERATION);
+        CompactEntity(generation << INDEX_BITS | index)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0 & INDEX_MASK
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.0 >> INDEX_BITS
+    }
+
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    // Every u32 is a valid index/generation pair so unlike `Entity::from_bits` this can't fail, generation 0
+    // is never handed out but all that means is `is_alive` says no to it
+    pub fn from_bits(bits: u32) -> CompactEntity {
+        CompactEntity(bits)
+    }
+
+    // Same as `Entity::from_raw_parts` except the index and generation have to fit in their 22 and 10 bits,
+    // panics if they don't
+    pub fn from_raw_parts(index: u32, generation: u32) -> CompactEntity {
+        if index > MAX_INDEX {
+            panic!(
+                "Attempted to create a compact entity with an index above {}",
+                MAX_INDEX
+            );
+        }
+        if generation > MAX_GENERATION {
+            panic!(
+                "Attempted to create a compact entity with a generation above {}",
+                MAX_GENERATION
+            );
+        }
+        CompactEntity::new(index, generation)
+    }
+}
+
+// Same as `Entity`, the `serde` feature writes these out as their `to_bits`
+#[cfg(feature = "serde")]
+impl serde::Serialize for CompactEntity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.to_bits())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CompactEntity {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<CompactEntity, D::Error> {
+        <u32 as serde::Deserialize>::deserialize(deserializer).map(CompactEntity::from_bits)
+    }
+}
+
+impl core::fmt::Display for CompactEntity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}v{}", self.index(), self.generation())
+    }
+}
+
+// Same idea as `EntityGenerator` but stripped down to spawn/despawn, generations are stored
+// as u16 since they never go above `MAX_GENERATION`
+// GUIDE: `World` only speaks `Entity` so nothing outside the tests spawns from this yet, it's here to pin
+// down how generations and tombstones work at 10 bits for whenever a compact world turns up
+pub(crate) struct CompactEntityGenerator {
+    entities: Vec<(u16, EntityStatus)>,
+    despawned: Vec<u32>, // Indices into entities vec
+    alive: usize,
+    tombstone_count: u32,
+}
+
+impl CompactEntityGenerator {
+    pub(crate) fn new() -> Self {
+        Self {
+            entities: Vec::new(),
+            despawned: Vec::new(),
+            alive: 0,
+            tombstone_count: 0,
+        }
+    }
+
+    pub(crate) fn alive_count(&self) -> usize {
+        self.alive
+    }
+
+    pub(crate) fn tombstone_count(&self) -> u32 {
+        self.tombstone_count
+    }
+
+    pub(crate) fn is_alive(&self, entity: CompactEntity) -> bool {
+        match self.entities.get(entity.index() as usize) {
+            Some(&(gen, status)) => {
+                gen as u32 == entity.generation() && status == EntityStatus::Alive
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn spawn(&mut self) -> CompactEntity {
+        match self.try_spawn() {
+            Ok(entity) => entity,
+            Err(SpawnError::Exhausted) => panic!("Too many entities spawned in world"),
+        }
+    }
+
+    pub(crate) fn try_spawn(&mut self) -> Result<CompactEntity, SpawnError> {
+        if let Some(despawned_idx) = self.despawned.pop() {
+            let (generation, status) = &mut self.entities[despawned_idx as usize];
+            assert_eq!(*status, EntityStatus::Dead);
+            // Slots at `MAX_GENERATION` are tombstoned by despawn so they never make it onto the free list
+            assert!((*generation as u32) < MAX_GENERATION);
+
+            *generation += 1;
+            *status = EntityStatus::Alive;
+            self.alive += 1;
+            return Ok(CompactEntity::new(despawned_idx, *generation as u32));
+        }
+
+        if self.entities.len() > MAX_INDEX as usize {
+            return Err(SpawnError::Exhausted);
+        }
+
+        self.entities.push((1, EntityStatus::Alive));
+        self.alive += 1;
+        Ok(CompactEntity::new(self.entities.len() as u32 - 1, 1))
+    }
+
+    pub(crate) fn despawn(&mut self, entity: CompactEntity) -> bool {
+        if self.is_alive(entity) == false {
+            return false;
+        }
+
+        let (generation, status) = &mut self.entities[entity.index() as usize];
+        self.alive -= 1;
+        match *generation as u32 == MAX_GENERATION {
+            true => {
+                *status = EntityStatus::Tombstone;
+                self.tombstone_count += 1;
+            }
+            false => {
+                *status = EntityStatus::Dead;
+                self.despawned.push(entity.index());
+            }
+        }
+        true
+    }
+}
+
+impl Default for CompactEntityGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompactEntity, CompactEntityGenerator, MAX_GENERATION, MAX_INDEX};
+    use crate::entity::{EntityStatus, SpawnError};
+
+    #[test]
+    fn packing_round_trip() {
+        let pairs = [
+            (0, 0),
+            (23, 4),
+            (MAX_INDEX, 0),
+            (0, MAX_GENERATION),
+            (MAX_INDEX, MAX_GENERATION),
+        ];
+        for &(index, generation) in pairs.iter() {
+            let e = CompactEntity::new(index, generation);
+            assert_eq!((e.index(), e.generation()), (index, generation));
+            assert_eq!(CompactEntity::from_bits(e.to_bits()), e);
+        }
+        assert_eq!(CompactEntity::new(23, 4).to_bits(), 4 << 22 | 23);
+        assert_eq!(
+            CompactEntity::new(MAX_INDEX, MAX_GENERATION).to_bits(),
+            u32::MAX
+        );
+        assert_eq!(std::mem::size_of::<CompactEntity>(), 4);
+    }
+
+    #[test]
+    fn from_raw_parts() {
+        let e = CompactEntity::from_raw_parts(23, 4);
+        assert_eq!(e, CompactEntity::new(23, 4));
+        assert_eq!(
+            CompactEntity::from_raw_parts(MAX_INDEX, MAX_GENERATION).to_bits(),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_raw_parts_index_too_big() {
+        CompactEntity::from_raw_parts(MAX_INDEX + 1, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_raw_parts_generation_too_big() {
+        CompactEntity::from_raw_parts(0, MAX_GENERATION + 1);
+    }
+
+    #[test]
+    fn spawn_despawn() {
+        let mut generator = CompactEntityGenerator::new();
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+        assert!(generator.is_alive(e1) && generator.is_alive(e2));
+
+        assert!(generator.despawn(e1));
+        assert!(generator.despawn(e1) == false);
+        let e3 = generator.spawn();
+        assert_eq!((e1.index(), e1.generation()), (0, 1));
+        assert_eq!((e3.index(), e3.generation()), (0, 2));
+        assert!(generator.is_alive(e1) == false);
+        assert_eq!(generator.alive_count(), 2);
+    }
+
+    #[test]
+    fn generation_overflow() {
+        let mut generator = CompactEntityGenerator::new();
+        let mut e = generator.spawn();
+        for generation in 1..MAX_GENERATION {
+            assert_eq!((e.index(), e.generation()), (0, generation));
+            generator.despawn(e);
+            e = generator.spawn();
+        }
+        assert_eq!((e.index(), e.generation()), (0, MAX_GENERATION));
+        assert_eq!(generator.tombstone_count(), 0);
+
+        // The generation can't go any higher so the slot gets retired
+        assert!(generator.despawn(e));
+        assert_eq!(generator.tombstone_count(), 1);
+        assert!(generator.is_alive(e) == false);
+        let fresh = generator.spawn();
+        assert_eq!((fresh.index(), fresh.generation()), (1, 1));
+    }
+
+    #[test]
+    fn exhausted() {
+        // Only ~4 million slots so unlike `EntityGenerator` we can actually run out
+        let mut generator = CompactEntityGenerator::new();
+        generator.entities = vec![(1, EntityStatus::Alive); MAX_INDEX as usize];
+        generator.alive = MAX_INDEX as usize;
+
+        let last = generator.try_spawn().unwrap();
+        assert_eq!(last.index(), MAX_INDEX);
+        assert_eq!(generator.try_spawn(), Err(SpawnError::Exhausted));
+
+        // A tombstoned slot doesn't help, a dead one does
+        generator.entities[0].0 = MAX_GENERATION as u16;
+        generator.despawn(CompactEntity::new(0, MAX_GENERATION));
+        assert_eq!(generator.try_spawn(), Err(SpawnError::Exhausted));
+        generator.despawn(last);
+        assert_eq!(generator.try_spawn(), Ok(CompactEntity::new(MAX_INDEX, 2)));
+        assert_eq!(generator.try_spawn(), Err(SpawnError::Exhausted));
+    }
+}
@@ -1,181 +1,2923 @@
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+// An entity *is* its index and generation, that's all that equality, hashing and ordering look at. The world
+// id is only there so the generator can turn away entities from other worlds, it isn't part of the identity
+// or a handle loaded with `from_bits` would never match the one it was saved from
+// `repr(C)` so the layout can be relied on when passing entities by value over FFI, that's a u32 index,
+// a u32 generation and then the u64 world id for 16 bytes in total
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
 pub struct Entity {
     pub(crate) index: u32,
     // GUIDE: trade offs of u16 vs u32 generation, why do we need a generation
-    generation: u32,
+    // Generations start at 1 so that `Option<Entity>` can use 0 as its `None` and stay the same size as `Entity`
+    generation: NonZeroU32,
+    world: WorldId,
+}
+
+impl PartialEq for Entity {
+    fn eq(&self, other: &Entity) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl Eq for Entity {}
+
+impl core::hash::Hash for Entity {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+// Sorts by index and then by generation, it's only there so that collections of entities can be sorted
+// deterministically and has no gameplay meaning
+impl PartialOrd for Entity {
+    fn partial_cmp(&self, other: &Entity) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entity {
+    fn cmp(&self, other: &Entity) -> core::cmp::Ordering {
+        (self.index, self.generation).cmp(&(other.index, other.generation))
+    }
+}
+
+// Every generator gets its own id so that an entity spawned in one world can't be mistaken for an entity
+// in another world that happens to have the same index and generation
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[repr(transparent)]
+pub(crate) struct WorldId(u64);
+
+static NEXT_WORLD_ID: AtomicU64 = AtomicU64::new(1);
+
+impl WorldId {
+    // Entities that didn't come from a generator (e.g. `Entity::from_bits`) aren't tied to any world,
+    // generators only check their index and generation
+    pub(crate) const NONE: WorldId = WorldId(0);
+
+    fn next() -> WorldId {
+        WorldId(NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    // For bringing back a world id from a snapshot, makes sure `next` never hands it out to another generator
+    fn reuse(id: WorldId) -> WorldId {
+        NEXT_WORLD_ID.fetch_max(id.0 + 1, Ordering::Relaxed);
+        id
+    }
+}
+
+impl Entity {
+    // A stand in for "no entity yet" when `Option<Entity>` would be a pain. Index `u32::MAX` is one past the
+    // last index a generator can hand out so this is never alive anywhere
+    pub const PLACEHOLDER: Entity = Entity {
+        index: u32::MAX,
+        generation: NonZeroU32::MAX,
+        world: WorldId::NONE,
+    };
+
+    // These are read only so handing them out can't be used to forge an entity,
+    // you still need a generator to tell you whether the index/generation pair is alive
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.get()
+    }
+
+    // Packs the index into the low 32 bits and the generation into the high 32 bits,
+    // useful for writing entities out to save files. The world id isn't included so
+    // `from_bits` gives back an entity that any generator will check by index and generation
+    // GUIDE: this is also what the `serde` feature writes out. The world id is gone after a round trip
+    // but that's fine, it isn't part of `==`
+    pub fn to_bits(self) -> u64 {
+        (self.generation.get() as u64) << 32 | self.index as u64
+    }
+
+    // Panics if the generation is 0 as no generator ever hands those out, use `try_from_bits` for anything
+    // read from outside the program (a corrupt save file shouldn't be able to crash the game)
+    pub fn from_bits(bits: u64) -> Entity {
+        match Entity::try_from_bits(bits) {
+            Some(entity) => entity,
+            None => panic!("Attempted to create an entity with generation 0 from bits"),
+        }
+    }
+
+    // Same as `from_bits` except generation 0 gives back `None` instead of panicking
+    pub fn try_from_bits(bits: u64) -> Option<Entity> {
+        let generation = NonZeroU32::new((bits >> 32) as u32)?;
+        Some(Entity {
+            index: bits as u32,
+            generation,
+            world: WorldId::NONE,
+        })
+    }
+
+    // For entities coming back over FFI as a separate index and generation, read them back out with
+    // `index`/`generation`. Nothing checks that the pair was ever handed out, which is fine since
+    // `is_alive` will say no to anything made up. Like `from_bits` it isn't tied to a world and panics
+    // on generation 0
+    pub fn from_raw_parts(index: u32, generation: u32) -> Entity {
+        let generation = match NonZeroU32::new(generation) {
+            Some(generation) => generation,
+            None => panic!("Attempted to create an entity with generation 0 from raw parts"),
+        };
+        Entity {
+            index,
+            generation,
+            world: WorldId::NONE,
+        }
+    }
+}
+
+// The book's chapter 2 generator hands out plain u64 ids that are never reused, so each id is a slot that only
+// ever had its first generation. These map id `n` to index `n` at generation 1 and back, `to_bits` is no good for
+// this since a book id would unpack as generation 0
+impl Entity {
+    // None if the id doesn't fit in a u32 index
+    pub fn from_book_id(id: u64) -> Option<Entity> {
+        if id > u32::MAX as u64 {
+            return None;
+        }
+        Some(Entity {
+            index: id as u32,
+            generation: NonZeroU32::MIN,
+            world: WorldId::NONE,
+        })
+    }
+
+    // None if the slot has been reused, the book generator never reuses ids so it has no way to say that
+    pub fn to_book_id(self) -> Option<u64> {
+        match self.generation == NonZeroU32::MIN {
+            true => Some(self.index as u64),
+            false => None,
+        }
+    }
+}
+
+// Prints as `23v4` for index 23 generation 4, the derived Debug impl is still there if you want the full struct
+impl core::fmt::Display for Entity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+// With the `serde` feature entities are written as their `to_bits` u64 rather than a struct, there's no
+// point saving the world id and it keeps the format the same as anyone already storing `to_bits`.
+// Generation 0 is an error instead of a panic since it came from outside the program
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.to_bits())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Entity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Entity, D::Error> {
+        let bits = <u64 as serde::Deserialize>::deserialize(deserializer)?;
+        Entity::try_from_bits(bits)
+            .ok_or_else(|| serde::de::Error::custom("entity has generation 0"))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum EntityStatus {
+    Alive,
+    Dead,
+    // Out of generations for good. The slot keeps the last generation it handed out (or had burned) however it
+    // got here, so the last handle to it reads as despawned and anything older as stale
+    Tombstone,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum DespawnError {
+    // The entity's index has never been spawned by this generator
+    OutOfBounds,
+    // The entity was spawned by a different generator
+    ForeignWorld,
+    // The slot has been reused since this entity was spawned
+    StaleGeneration,
+    AlreadyDead,
+}
+
+// A more detailed answer than `is_alive` for when you need to know *why* an entity isn't alive
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum Liveness {
+    Alive,
+    Despawned,
+    // The slot has been reused since this entity was spawned
+    StaleGeneration,
+    // The entity's index has never been spawned by this generator
+    OutOfRange,
+    // The entity was spawned by a different generator
+    ForeignWorld,
 }
 
+// Mirrors `EntityStatus` for diagnostics so that the real status enum can change without breaking them
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum EntityStatus {
+pub(crate) enum SlotStatus {
     Alive,
     Dead,
     Tombstone,
 }
 
+impl From<EntityStatus> for SlotStatus {
+    fn from(status: EntityStatus) -> SlotStatus {
+        match status {
+            EntityStatus::Alive => SlotStatus::Alive,
+            EntityStatus::Dead => SlotStatus::Dead,
+            EntityStatus::Tombstone => SlotStatus::Tombstone,
+        }
+    }
+}
+
+// Everything you'd want to dump out once a frame to keep an eye on the generator
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) struct GeneratorStats {
+    pub(crate) alive: usize,
+    // Slots on the free list that `spawn` will reuse before allocating new ones
+    pub(crate) dead_reusable: usize,
+    pub(crate) tombstoned: u32,
+    pub(crate) capacity: usize,
+    pub(crate) high_water_mark: u32,
+}
+
+// What happens when an entity from another world is checked or an entity is despawned twice. `Silent` just
+// gives back `false` like always, `Warn` does the same but prints to stderr the first time, `Panic` panics
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StrictMode {
+    Panic,
+    Warn,
+    Silent,
+}
+
+// How far a slot's generation moves on when it gets reused
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum GenStrategy {
+    Increment,
+    // Moves on by somewhere between 1 and `MAX_RANDOM_STEP` so the next generation of a slot can't be guessed
+    // from the last one. The same seed always gives the same steps so replays still line up
+    RandomStep { seed: u64 },
+}
+
+const MAX_RANDOM_STEP: u64 = 1024;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum SpawnError {
+    // Every index is either alive or tombstoned
+    Exhausted,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum RelocateError {
+    // The entity being moved isn't alive in this generator
+    NotAlive,
+    // There's already an alive entity at the new index
+    Occupied,
+    // The slot at the new index has used up all of its generations
+    Tombstone,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum SpawnAtError {
+    // There's already an alive entity in the slot with a different generation
+    AlreadyAlive,
+    // The slot has used up all of its generations and can't be reused
+    Tombstone,
+    // The entity was spawned by a different generator
+    ForeignWorld,
+}
+
+// Everything needed to rebuild a generator that hands out the same generations as the one it came from,
+// all plain data so it can be written out however the save file wants
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(crate) struct GeneratorSnapshot {
+    pub(crate) entities: Vec<(NonZeroU32, EntityStatus)>,
+    // The free list as it's stored in the generator, so reuse starts from the front with `fifo` and from the
+    // back otherwise. Slots that were still cooling down are at whichever end gets reused last
+    pub(crate) despawned: Vec<u32>,
+    pub(crate) fifo: bool,
+    pub(crate) world_id: WorldId,
+    // Where `GenStrategy::RandomStep` is up to, so the restored generator takes the same steps
+    pub(crate) generation_strategy: GenStrategy,
+    pub(crate) rng: u64,
+    // How many spawns a freed slot waits before it can be reused, see `with_recycle_delay`
+    pub(crate) recycle_delay: usize,
+    pub(crate) strict_mode: StrictMode,
+    pub(crate) record_despawns: bool,
+}
+
+// Allocation is deterministic, two generators put through the same sequence of calls hand out entities with the
+// same index and generation in the same order (so e.g. lockstep/rollback sims stay in sync). The only exception is
+// reserving entities from several threads at once since which thread gets which index comes down to timing.
+// Entities from the two generators will `==` each other but each generator still turns the other's away as foreign
 pub(crate) struct EntityGenerator {
-    entities: Vec<(u32, EntityStatus)>,
-    despawned: Vec<u32>, // Indices into entities vec
+    entities: Vec<(NonZeroU32, EntityStatus)>,
+    despawned: VecDeque<u32>, // Indices into entities vec
+    // Slots freed by the last few spawns that aren't allowed back on the free list yet, one bucket per
+    // spawn with the oldest at the front. Empty unless made with `with_recycle_delay`
+    cooling: VecDeque<Vec<u32>>,
+    // GUIDE: reusing the most recently despawned slot means a hot slot can burn through its generations
+    // way faster than the rest, recycling in FIFO order spreads that wear out across all the slots
+    fifo: bool,
+    alive: usize,
+    // The most entities that have ever been alive at once
+    peak_alive: u32,
+    tombstone_count: u32,
+    world_id: WorldId,
+    // How many entities have been handed out by `reserve_entity` past the end of `entities`
+    // that haven't been written into it yet by `flush`
+    reserved: AtomicU32,
+    // Every entity that's been despawned since the last `drain_despawned`, only recorded once turned on with
+    // `set_record_despawns`. Nothing clears this for you so once it's on it has to be drained or it keeps growing
+    despawn_events: Vec<Entity>,
+    record_despawns: bool,
+    // Called with every entity handed out by `spawn`/`spawn_batch`, useful for tracking down leaks
+    // GUIDE: Send + Sync so that the generator can still be shared across threads for `reserve_entity`
+    spawn_observer: Option<Box<dyn FnMut(Entity) + Send + Sync>>,
+    // Called with the index of every slot that gets tombstoned, whether that's from despawning at the last
+    // generation, a spawn that would step past it, `burn_generation` or `relocate`
+    tombstone_observer: Option<Box<dyn FnMut(u32) + Send + Sync>>,
+    strict_mode: StrictMode,
+    // Set the first time `StrictMode::Warn` prints something so it only happens once
+    warned: AtomicBool,
+    generation_strategy: GenStrategy,
+    // xorshift state for `GenStrategy::RandomStep`, unused otherwise
+    rng: u64,
 }
 
 impl EntityGenerator {
     pub(crate) fn new() -> Self {
         Self {
-            // GUIDE: We could do with_capacity here if we wanted
             entities: Vec::new(),
-            despawned: Vec::new(),
+            despawned: VecDeque::new(),
+            cooling: VecDeque::new(),
+            fifo: false,
+            alive: 0,
+            peak_alive: 0,
+            tombstone_count: 0,
+            world_id: WorldId::next(),
+            reserved: AtomicU32::new(0),
+            despawn_events: Vec::new(),
+            record_despawns: false,
+            spawn_observer: None,
+            tombstone_observer: None,
+            strict_mode: StrictMode::Silent,
+            warned: AtomicBool::new(false),
+            generation_strategy: GenStrategy::Increment,
+            rng: 0,
         }
     }
 
-    pub(crate) fn is_alive(&self, entity: Entity) -> bool {
-        // GUIDE: talk about moment where the generation field was unused and it made me realise there was a bug
-        if let Some(&(gen, status)) = self.entities.get(entity.index as usize) {
-            if gen == entity.generation && status == EntityStatus::Alive {
-                return true;
-            }
+    pub(crate) fn new_fifo() -> Self {
+        Self {
+            fifo: true,
+            ..Self::new()
         }
-        false
     }
 
-    pub(crate) fn spawn(&mut self) -> Entity {
-        if let Some(despawned_idx) = self.despawned.pop() {
-            // GUIDE: talk about correctness of this case and the implicit assumption of usize > u32 throughout this module
-            let (generation, status) = &mut self.entities[despawned_idx as usize];
-            assert_eq!(*status, EntityStatus::Dead);
-            assert!(*generation != u32::MAX); // GUIDE: Explain tombstones and why wrapping generation could be problematic
+    // Despawned slots only become reusable after `n` more spawns so that a stale handle keeps failing
+    // `is_alive` for a while instead of the slot being handed straight back out, mostly useful while debugging
+    pub(crate) fn with_recycle_delay(n: usize) -> Self {
+        Self {
+            cooling: (0..n).map(|_| Vec::new()).collect(),
+            ..Self::new()
+        }
+    }
 
-            *generation = *generation + 1; // We use regular + addition here instead of wrapping or saturating etc because we checked for != u32::MAX
-            *status = EntityStatus::Alive;
+    pub(crate) fn with_generation_strategy(strategy: GenStrategy) -> Self {
+        let rng = match strategy {
+            GenStrategy::Increment => 0,
+            // xorshift gets stuck on 0 forever so swap it for something else
+            GenStrategy::RandomStep { seed: 0 } => 0x9E37_79B9_7F4A_7C15,
+            GenStrategy::RandomStep { seed } => seed,
+        };
+        Self {
+            generation_strategy: strategy,
+            rng,
+            ..Self::new()
+        }
+    }
 
-            return Entity {
-                index: despawned_idx as u32, // This cast wont lead to issues because we check to never spawn more than u32::MAX entities
-                generation: *generation,
-            };
+    pub(crate) fn with_capacity(n: usize) -> Self {
+        Self {
+            entities: Vec::with_capacity(n),
+            // GUIDE: how many entities get despawned at once is a guess, a quarter seems like it wont waste too much
+            despawned: VecDeque::with_capacity(n / 4),
+            ..Self::new()
         }
+    }
 
-        // We only use a u32 for the index portion of entity's so spawning more than that is impossible
-        if self.entities.len() == u32::MAX as usize {
-            panic!("Too many entities spawned in world");
+    // We keep a running count rather than scanning `entities` so that this is O(1)
+    pub(crate) fn alive_count(&self) -> usize {
+        self.alive + self.reserved() as usize
+    }
+
+    // Dead and tombstoned slots dont count, only alive entities
+    pub(crate) fn is_empty(&self) -> bool {
+        self.alive_count() == 0
+    }
+
+    // The number of allocated slots, including ones that are dead or tombstoned
+    pub(crate) fn capacity(&self) -> usize {
+        self.entities.len()
+    }
+
+    // How many spawns can be served from the free list before new slots have to be allocated
+    pub(crate) fn reusable_slots(&self) -> usize {
+        self.despawned.len()
+    }
+
+    // The free list as it's stored, slots are reused from the front with FIFO recycling and from the back
+    // otherwise. Slots still cooling down from a recycle delay aren't on it yet, see `cooling_slots`
+    // GUIDE: the free list is a `VecDeque` so that FIFO recycling can pop off the front, which means it has to
+    // be shuffled into one contiguous piece first and that needs a `&mut self`
+    pub(crate) fn free_list(&mut self) -> &[u32] {
+        self.despawned.make_contiguous()
+    }
+
+    // The slots waiting out a recycle delay, one bucket per spawn still to go with the bucket that goes on the
+    // free list next first. Empty unless made with `with_recycle_delay`
+    pub(crate) fn cooling_slots(&self) -> impl Iterator<Item = &[u32]> {
+        self.cooling.iter().map(|bucket| bucket.as_slice())
+    }
+
+    // Tombstoned slots are never reused so if this keeps growing something is hammering a few slots
+    pub(crate) fn tombstone_count(&self) -> u32 {
+        self.tombstone_count
+    }
+
+    // Alias of `tombstone_count` named for how many of the `u32::MAX` indices are gone for good, watching it
+    // over time tells you roughly when you'll need to `compact` (which drops every tombstone) or restart
+    pub(crate) fn lost_capacity(&self) -> u32 {
+        self.tombstone_count()
+    }
+
+    // The fraction of slots sitting on the free list waiting to be reused, tombstones don't count
+    // since there's nothing that can be done with them
+    pub(crate) fn fragmentation_ratio(&self) -> f32 {
+        match self.entities.is_empty() {
+            true => 0.0,
+            false => self.despawned.len() as f32 / self.entities.len() as f32,
         }
+    }
 
-        self.entities.push((0, EntityStatus::Alive));
-        Entity {
-            index: self.entities.len() as u32 - 1,
-            generation: 0,
+    // Never goes down so it's the worst case you've seen, handy for sizing buffers up front
+    pub(crate) fn high_water_mark(&self) -> u32 {
+        self.peak_alive
+    }
+
+    pub(crate) fn stats(&self) -> GeneratorStats {
+        GeneratorStats {
+            alive: self.alive_count(),
+            dead_reusable: self.despawned.len(),
+            tombstoned: self.tombstone_count,
+            capacity: self.capacity(),
+            high_water_mark: self.high_water_mark(),
         }
     }
 
-    pub(crate) fn despawn(&mut self, entity: Entity) -> bool {
-        // GUIDE: this code originally used [] indexing because I assumed it was okay but forgot we could get entities from other worlds
-        // I discovered this when writing tests
-        let (gen, status) = match self.entities.get_mut(entity.index as usize) {
-            Some(d) => d,
-            None => return false,
-        };
+    // Anything that makes entities alive goes through here so that we can keep track of the peak
+    fn add_alive(&mut self, n: usize) {
+        self.alive += n;
+        self.peak_alive = self.peak_alive.max(self.alive as u32);
+    }
 
-        // We could hard error when despawning an already despawned entity except that this would cause
-        // users to have to wrap every call to this fn in a call to is_alive which would be pretty unergonomic
-        if *status != EntityStatus::Alive {
-            return false;
+    fn entity(&self, index: u32, generation: NonZeroU32) -> Entity {
+        Entity {
+            index,
+            generation,
+            world: self.world_id,
         }
+    }
 
-        match *gen == u32::MAX {
-            true => *status = EntityStatus::Tombstone,
-            false => {
-                *status = EntityStatus::Dead;
-                self.despawned.push(entity.index)
+    pub(crate) fn is_foreign(&self, entity: Entity) -> bool {
+        entity.world != WorldId::NONE && entity.world != self.world_id
+    }
+
+    fn reserved(&self) -> u32 {
+        self.reserved.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_spawn_observer(
+        &mut self,
+        observer: impl FnMut(Entity) + Send + Sync + 'static,
+    ) {
+        self.spawn_observer = Some(Box::new(observer));
+    }
+
+    pub(crate) fn set_tombstone_observer(
+        &mut self,
+        observer: impl FnMut(u32) + Send + Sync + 'static,
+    ) {
+        self.tombstone_observer = Some(Box::new(observer));
+    }
+
+    pub(crate) fn set_strict_mode(&mut self, mode: StrictMode) {
+        self.strict_mode = mode;
+    }
+
+    // Without the `std` feature there's nowhere to print to so `Warn` is the same as `Silent`
+    fn report_misuse(&self, what: core::fmt::Arguments<'_>) {
+        match self.strict_mode {
+            StrictMode::Panic => panic!("Attempted to {}", what),
+            StrictMode::Warn => {
+                if self.warned.swap(true, Ordering::Relaxed) == false {
+                    #[cfg(feature = "std")]
+                    eprintln!(
+                        "safecs: attempted to {} (only the first warning is printed)",
+                        what
+                    );
+                }
             }
+            StrictMode::Silent => (),
         }
-        true
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::EntityGenerator;
-    use super::{Entity, EntityStatus};
+    // `None` when the step would go past the last generation, the slot should be tombstoned then
+    fn next_generation(&mut self, generation: NonZeroU32) -> Option<NonZeroU32> {
+        let step = match self.generation_strategy {
+            GenStrategy::Increment => 1,
+            GenStrategy::RandomStep { .. } => {
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 7;
+                self.rng ^= self.rng << 17;
+                1 + (self.rng % MAX_RANDOM_STEP) as u32
+            }
+        };
+        generation.checked_add(step)
+    }
 
-    #[test]
-    fn spawn_one() {
-        let mut generator = EntityGenerator::new();
-        let e1 = generator.spawn();
+    fn observe_spawn(&mut self, entity: Entity) {
+        if let Some(observer) = &mut self.spawn_observer {
+            observer(entity);
+        }
+    }
 
-        assert!(generator.is_alive(e1));
+    fn observe_tombstone(&mut self, index: u32) {
+        if let Some(observer) = &mut self.tombstone_observer {
+            observer(index);
+        }
+    }
 
-        generator.despawn(e1);
-        assert!(generator.is_alive(e1) == false);
+    // Puts a dead slot on the free list, or in the newest cooling bucket if there's a recycle delay
+    fn free(&mut self, index: u32) {
+        match self.cooling.back_mut() {
+            Some(bucket) => bucket.push(index),
+            None => self.despawned.push_back(index),
+        }
     }
 
-    #[test]
-    fn other_world_entity() {
-        let mut generator_1 = EntityGenerator::new();
-        let mut generator_2 = EntityGenerator::new();
+    // For when a dead slot gets used for something other than a spawn, it might still be cooling down
+    fn unfree(&mut self, index: u32) {
+        self.despawned.retain(|&idx| idx != index);
+        for bucket in self.cooling.iter_mut() {
+            bucket.retain(|&idx| idx != index);
+        }
+    }
 
-        let e1_1 = generator_1.spawn();
-        assert!(generator_2.is_alive(e1_1) == false);
-        assert!(generator_2.despawn(e1_1) == false);
+    // Called after every spawn, the oldest bucket has now waited out the delay
+    fn tick_recycle_delay(&mut self) {
+        if let Some(mut ready) = self.cooling.pop_front() {
+            self.despawned.extend(ready.drain(..));
+            self.cooling.push_back(ready);
+        }
+    }
 
-        // GUIDE: maybe we should test that despawn call didnt mess with the next spawned entity
-        let e1_2 = generator_2.spawn();
-        assert_eq!(e1_1, e1_2);
+    // Reserved entities always start at the first generation in the slots just past the end of `entities`
+    fn is_reserved(&self, entity: Entity) -> bool {
+        let len = self.entities.len() as u64;
+        let index = entity.index as u64;
+        entity.generation == NonZeroU32::MIN && index >= len && index < len + self.reserved() as u64
     }
 
-    #[test]
-    fn generation_reuse() {
-        let mut generator_1 = EntityGenerator::new();
+    // GUIDE: someone asked for an `unsafe fn is_alive_unchecked` using `get_unchecked` for hot loops, but safecs is
+    // `forbid(unsafe_code)` and that's kind of the whole point of it. If you're checking entities you just got from
+    // `iter_alive` you don't need to check them at all, and otherwise the bounds check is one well predicted branch
+    pub(crate) fn is_alive(&self, entity: Entity) -> bool {
+        if self.is_foreign(entity) {
+            self.report_misuse(format_args!(
+                "check whether {} from another world is alive",
+                entity
+            ));
+            return false;
+        }
+        self.is_alive_quiet(entity)
+    }
 
-        let e1 = generator_1.spawn();
-        generator_1.despawn(e1);
-        let e2 = generator_1.spawn();
+    // Same answer as `is_alive` but a foreign entity is never reported to `StrictMode`, for callers like
+    // `World::get_weak` where being handed any old entity is expected
+    pub(crate) fn is_alive_quiet(&self, entity: Entity) -> bool {
+        if entity == Entity::PLACEHOLDER || self.is_foreign(entity) {
+            return false;
+        }
 
-        assert_eq!(
-            e2,
-            Entity {
-                index: 0,
-                generation: 1,
+        // GUIDE: talk about moment where the generation field was unused and it made me realise there was a bug
+        if let Some(&(gen, status)) = self.entities.get(entity.index as usize) {
+            if gen == entity.generation && status == EntityStatus::Alive {
+                return true;
             }
-        );
+        }
+        self.is_reserved(entity)
     }
 
-    #[test]
-    fn tombstone() {
-        let mut generator_1 = EntityGenerator {
-            entities: vec![(u32::MAX, EntityStatus::Alive)],
-            despawned: Vec::new(),
-        };
+    // `out` is cleared first so the same buffer can be reused every frame
+    pub(crate) fn are_alive(&self, entities: &[Entity], out: &mut Vec<bool>) {
+        out.clear();
+        out.extend(entities.iter().map(|&entity| self.is_alive(entity)));
+    }
 
-        let e1 = Entity {
-            index: 0,
-            generation: u32::MAX,
-        };
+    // The alive entity currently in `index` if there is one, for putting generations back onto old
+    // data that only kept the index around. Reserved entities count as alive here too
+    pub(crate) fn entity_at_index(&self, index: u32) -> Option<Entity> {
+        match self.entities.get(index as usize) {
+            Some(&(generation, EntityStatus::Alive)) => Some(self.entity(index, generation)),
+            Some(_) => None,
+            None => {
+                let entity = self.entity(index, NonZeroU32::MIN);
+                match self.is_reserved(entity) {
+                    true => Some(entity),
+                    false => None,
+                }
+            }
+        }
+    }
 
-        // GUIDE: lets just be really sure we manually created the entity correctly
-        assert!(generator_1.is_alive(e1));
+    pub(crate) fn liveness(&self, entity: Entity) -> Liveness {
+        if self.is_foreign(entity) {
+            return Liveness::ForeignWorld;
+        }
 
-        generator_1.despawn(e1);
-        // GUIDE: probably dont need to but might aswell check that tombstone entities are considered dead
-        assert!(generator_1.is_alive(e1) == false);
-        assert_eq!(generator_1.entities[0], (u32::MAX, EntityStatus::Tombstone));
+        match self.entities.get(entity.index as usize) {
+            None if self.is_reserved(entity) => Liveness::Alive,
+            None => Liveness::OutOfRange,
+            Some(&(gen, _)) if gen != entity.generation => Liveness::StaleGeneration,
+            Some(&(_, EntityStatus::Alive)) => Liveness::Alive,
+            Some(_) => Liveness::Despawned,
+        }
+    }
 
-        let e2 = generator_1.spawn();
-        assert!(generator_1.is_alive(e2));
-        assert_eq!(
-            e2,
-            Entity {
-                index: 1,
-                generation: 0,
-            }
-        );
+    // For tests and debug code, `let e = generator.assert_alive(e);` instead of asserting and then using it
+    pub(crate) fn assert_alive(&self, entity: Entity) -> Entity {
+        match self.liveness(entity) {
+            Liveness::Alive => entity,
+            Liveness::Despawned => panic!("Attempted to use despawned entity {}", entity),
+            Liveness::StaleGeneration => panic!(
+                "Attempted to use entity {} but its slot has been reused since",
+                entity
+            ),
+            Liveness::OutOfRange => panic!(
+                "Attempted to use entity {} but its index was never spawned",
+                entity
+            ),
+            Liveness::ForeignWorld => panic!(
+                "Attempted to use entity {} which is from a different world",
+                entity
+            ),
+        }
     }
 
-    #[test]
-    fn double_despawn() {
-        let mut generator_1 = EntityGenerator::new();
-        let e1 = generator_1.spawn();
-        assert!(generator_1.despawn(e1) == true);
-        assert!(generator_1.despawn(e1) == false);
+    pub(crate) fn iter_alive(&self) -> impl Iterator<Item = Entity> + '_ {
+        let len = self.entities.len() as u32;
+        self.entities
+            .iter()
+            .enumerate()
+            .filter(|(_, &(_, status))| status == EntityStatus::Alive)
+            // We never have more than u32::MAX entities so this cant truncate
+            .map(move |(index, &(generation, _))| self.entity(index as u32, generation))
+            .chain(
+                (len..len + self.reserved()).map(move |index| self.entity(index, NonZeroU32::MIN)),
+            )
+    }
 
-        // GUIDE: despawning pushes an entity to the Despawned vec... maybe we should check theres only one entry after calling that twice
-        assert!(generator_1.despawned.len() == 1);
+    // Every allocated slot as `(index, generation, status)` whether it's alive or not, for inspecting how
+    // fragmented the generator is. Reserved entities haven't got a slot yet so they don't show up until `flush`
+    pub(crate) fn slots(&self) -> impl Iterator<Item = (u32, u32, SlotStatus)> + '_ {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(index, &(generation, status))| (index as u32, generation.get(), status.into()))
+    }
+
+    // One bit per index, set if that index is alive, so it can be ANDed with other bitsets to find alive entities
+    // that are in them. Reserved entities count as alive here too
+    pub(crate) fn alive_bitset(&self) -> Vec<u64> {
+        let len = self.entities.len() + self.reserved() as usize;
+        let mut bits = vec![0; len.div_ceil(64)];
+        for entity in self.iter_alive() {
+            bits[entity.index as usize / 64] |= 1 << (entity.index % 64);
+        }
+        bits
+    }
+
+    // Any part of the range past the allocated slots is just ignored (apart from reserved entities)
+    pub(crate) fn count_alive_in_range(&self, range: core::ops::Range<u32>) -> usize {
+        let len = self.entities.len() as u32;
+        let start = range.start.min(len);
+        let end = range.end.min(len).max(start);
+        let allocated = self.entities[start as usize..end as usize]
+            .iter()
+            .filter(|&&(_, status)| status == EntityStatus::Alive)
+            .count();
+        let reserved_end = len + self.reserved();
+        let reserved = range
+            .end
+            .min(reserved_end)
+            .saturating_sub(range.start.max(len));
+        allocated + reserved as usize
+    }
+
+    // Drops any entities that have been despawned since they were handed out, handy for systems
+    // that keep around a cached list of entities they care about
+    pub(crate) fn filter_alive<'a>(
+        &'a self,
+        it: impl Iterator<Item = Entity> + 'a,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        it.filter(move |&entity| self.is_alive(entity))
+    }
+
+    // Hands out an entity without needing `&mut self` so that anything only holding a shared reference
+    // (e.g. a command buffer) can still get an entity to refer to. The entity counts as alive straight
+    // away but isn't actually written into `entities` until `flush` is called
+    // GUIDE: we could reuse despawned slots here too but that would need the free list to be atomic as well
+    pub(crate) fn reserve_entity(&self) -> Entity {
+        let offset = self.reserved.fetch_add(1, Ordering::Relaxed);
+        let index = self.entities.len() as u64 + offset as u64;
+        if index >= u32::MAX as u64 {
+            panic!("Too many entities spawned in world");
+        }
+        self.entity(index as u32, NonZeroU32::MIN)
+    }
+
+    // Reserves a contiguous range of n entities in one go, the iterator doesn't borrow the generator
+    // so it stays valid no matter what gets reserved after it
+    pub(crate) fn reserve_entities(&self, n: u32) -> impl Iterator<Item = Entity> {
+        let offset = self.reserved.fetch_add(n, Ordering::Relaxed);
+        let start = self.entities.len() as u64 + offset as u64;
+        if start + n as u64 > u32::MAX as u64 {
+            panic!("Too many entities spawned in world");
+        }
+
+        let world = self.world_id;
+        (start as u32..start as u32 + n).map(move |index| Entity {
+            index,
+            generation: NonZeroU32::MIN,
+            world,
+        })
+    }
+
+    // Writes any reserved entities into `entities`, anything that pushes to `entities` has to call
+    // this first otherwise it would hand out the same index as a reserved entity
+    pub(crate) fn flush(&mut self) {
+        let reserved = core::mem::replace(self.reserved.get_mut(), 0) as usize;
+        self.entities.resize(
+            self.entities.len() + reserved,
+            (NonZeroU32::MIN, EntityStatus::Alive),
+        );
+        self.add_alive(reserved);
+    }
+
+    // Panics if every index has been used up, see `try_spawn` if you'd rather handle that
+    pub(crate) fn spawn(&mut self) -> Entity {
+        match self.try_spawn() {
+            Ok(entity) => entity,
+            Err(SpawnError::Exhausted) => panic!("Too many entities spawned in world"),
+        }
+    }
+
+    pub(crate) fn try_spawn(&mut self) -> Result<Entity, SpawnError> {
+        self.flush();
+        loop {
+            let despawned_idx = match self.fifo {
+                true => self.despawned.pop_front(),
+                false => self.despawned.pop_back(),
+            };
+            let despawned_idx = match despawned_idx {
+                Some(despawned_idx) => despawned_idx,
+                None => break,
+            };
+
+            // GUIDE: talk about correctness of this case and the implicit assumption of usize > u32 throughout this module
+            let (generation, status) = self.entities[despawned_idx as usize];
+            assert_eq!(status, EntityStatus::Dead);
+
+            // GUIDE: Explain tombstones and why wrapping generation could be problematic
+            // `kill` tombstones slots at the last generation but a random step can still overshoot from below
+            // it (as can a hand edited snapshot), when that happens we retire the slot here and try the next one
+            match self.next_generation(generation) {
+                Some(next) => {
+                    self.entities[despawned_idx as usize] = (next, EntityStatus::Alive);
+                    self.add_alive(1);
+                    let entity = self.entity(despawned_idx, next);
+                    self.observe_spawn(entity);
+                    self.tick_recycle_delay();
+                    return Ok(entity);
+                }
+                None => {
+                    self.entities[despawned_idx as usize].1 = EntityStatus::Tombstone;
+                    self.tombstone_count += 1;
+                    self.observe_tombstone(despawned_idx);
+                }
+            }
+        }
+
+        // We only use a u32 for the index portion of entity's so spawning more than that is impossible,
+        // by this point every free slot has been used or tombstoned so there's nowhere left to go
+        if self.entities.len() == u32::MAX as usize {
+            return Err(SpawnError::Exhausted);
+        }
+
+        self.entities.push((NonZeroU32::MIN, EntityStatus::Alive));
+        self.add_alive(1);
+        // This cast wont lead to issues because we check to never spawn more than u32::MAX entities
+        let entity = self.entity(self.entities.len() as u32 - 1, NonZeroU32::MIN);
+        self.observe_spawn(entity);
+        self.tick_recycle_delay();
+        Ok(entity)
+    }
+
+    // Behaves the same as calling spawn n times, freed slots get reused first and then
+    // the rest of the entities are allocated in one go at the end of the entities vec
+    pub(crate) fn spawn_batch(&mut self, n: usize) -> Vec<Entity> {
+        self.flush();
+        let mut spawned = Vec::with_capacity(n);
+        // Slots can come off cooldown part way through the batch so with a recycle delay it's one at a time
+        while spawned.len() < n
+            && (self.despawned.is_empty() == false || self.cooling.is_empty() == false)
+        {
+            spawned.push(self.spawn());
+        }
+
+        let fresh = n - spawned.len();
+        if self.entities.len() + fresh > u32::MAX as usize {
+            panic!("Too many entities spawned in world");
+        }
+
+        let start = self.entities.len() as u32;
+        self.entities.resize(
+            self.entities.len() + fresh,
+            (NonZeroU32::MIN, EntityStatus::Alive),
+        );
+        self.add_alive(fresh);
+        for index in start..start + fresh as u32 {
+            let entity = self.entity(index, NonZeroU32::MIN);
+            self.observe_spawn(entity);
+            spawned.push(entity);
+        }
+        spawned
+    }
+
+    // Spawns the exact entity given rather than allocating a new one, mostly useful for recreating
+    // entities with the same index and generation when loading a saved world
+    pub(crate) fn spawn_at(&mut self, entity: Entity) -> Result<(), SpawnAtError> {
+        if self.is_foreign(entity) {
+            return Err(SpawnAtError::ForeignWorld);
+        }
+        self.flush();
+
+        let index = entity.index as usize;
+        if index >= self.entities.len() {
+            if index >= u32::MAX as usize {
+                panic!("Too many entities spawned in world");
+            }
+
+            // Any slots we skip over are free for `spawn` to use later
+            for skipped in self.entities.len()..index {
+                self.entities.push((NonZeroU32::MIN, EntityStatus::Dead));
+                self.despawned.push_back(skipped as u32);
+            }
+            self.entities.push((entity.generation, EntityStatus::Alive));
+            self.add_alive(1);
+            return Ok(());
+        }
+
+        let (generation, status) = &mut self.entities[index];
+        match *status {
+            EntityStatus::Alive if *generation == entity.generation => return Ok(()),
+            EntityStatus::Alive => return Err(SpawnAtError::AlreadyAlive),
+            EntityStatus::Tombstone => return Err(SpawnAtError::Tombstone),
+            EntityStatus::Dead => (),
+        }
+
+        *generation = entity.generation;
+        *status = EntityStatus::Alive;
+        self.add_alive(1);
+        self.unfree(entity.index);
+        Ok(())
+    }
+
+    // Idempotent version of `spawn_at` for replaying the same entities over and over, returns
+    // whether the entity had to be spawned. Anything `spawn_at` would refuse is handed back as is,
+    // a replay that hits one has desynced but that's for the caller to decide what to do about
+    pub(crate) fn get_or_spawn(&mut self, entity: Entity) -> Result<bool, SpawnAtError> {
+        if self.is_alive(entity) {
+            return Ok(false);
+        }
+        self.spawn_at(entity).map(|()| true)
+    }
+
+    pub(crate) fn despawn(&mut self, entity: Entity) -> bool {
+        // We could hard error when despawning an already despawned entity except that this would cause
+        // users to have to wrap every call to this fn in a call to is_alive which would be pretty unergonomic.
+        // If you do want to hear about it then that's what `StrictMode` is for
+        let err = match self.try_despawn(entity) {
+            Ok(()) => return true,
+            Err(err) => err,
+        };
+        match err {
+            DespawnError::ForeignWorld => {
+                self.report_misuse(format_args!("despawn {} from another world", entity))
+            }
+            DespawnError::OutOfBounds => {
+                self.report_misuse(format_args!("despawn {} which was never spawned", entity))
+            }
+            DespawnError::StaleGeneration | DespawnError::AlreadyDead => {
+                self.report_misuse(format_args!("despawn {} which is already dead", entity))
+            }
+        }
+        false
+    }
+
+    pub(crate) fn try_despawn(&mut self, entity: Entity) -> Result<(), DespawnError> {
+        if self.is_foreign(entity) {
+            return Err(DespawnError::ForeignWorld);
+        }
+        self.flush();
+
+        // GUIDE: this code originally used [] indexing because I assumed it was okay but forgot we could get entities from other worlds
+        // I discovered this when writing tests
+        let &(gen, status) = match self.entities.get(entity.index as usize) {
+            Some(d) => d,
+            None => return Err(DespawnError::OutOfBounds),
+        };
+
+        // GUIDE: despawn used to only look at the status so an old handle could despawn whoever was reusing the slot
+        if gen != entity.generation {
+            return Err(DespawnError::StaleGeneration);
+        }
+        if status != EntityStatus::Alive {
+            return Err(DespawnError::AlreadyDead);
+        }
+
+        self.kill(entity.index);
+        Ok(())
+    }
+
+    // Marks an alive slot as dead, or as a tombstone if its generation can't be bumped anymore
+    fn kill(&mut self, index: u32) {
+        let (generation, status) = &mut self.entities[index as usize];
+        assert_eq!(*status, EntityStatus::Alive);
+
+        self.alive -= 1;
+        let entity = Entity {
+            index,
+            generation: *generation,
+            world: self.world_id,
+        };
+        if self.record_despawns {
+            self.despawn_events.push(entity);
+        }
+        match *generation == NonZeroU32::MAX {
+            true => {
+                *status = EntityStatus::Tombstone;
+                self.tombstone_count += 1;
+                self.observe_tombstone(index);
+            }
+            false => {
+                *status = EntityStatus::Dead;
+                self.free(index)
+            }
+        }
+    }
+
+    // Off by default, turning it off again throws away anything that hasn't been drained yet
+    pub(crate) fn set_record_despawns(&mut self, enabled: bool) {
+        self.record_despawns = enabled;
+        if enabled == false {
+            self.despawn_events = Vec::new();
+        }
+    }
+
+    // Hands back everything despawned since the last call, meant to be called once a frame to clean up
+    // anything living outside of the world (GPU buffers, audio handles, etc). Always empty unless
+    // `set_record_despawns` has been turned on
+    pub(crate) fn drain_despawned(&mut self) -> alloc::vec::Drain<'_, Entity> {
+        self.despawn_events.drain(..)
+    }
+
+    // Bumps a dead slot's generation without spawning anything in it so that the generation spawn would have
+    // handed out next is never used. A slot whose next spawn would go past the last generation gets tombstoned
+    pub(crate) fn burn_generation(&mut self, index: u32) {
+        self.flush();
+        let generation = match self.entities.get(index as usize) {
+            Some(&(generation, EntityStatus::Dead)) => generation,
+            Some(_) => panic!("Attempted to burn a generation on a slot that isn't dead"),
+            None => panic!("Attempted to burn a generation on a slot that was never spawned"),
+        };
+
+        // Steps the same way a spawn would so a random step strategy stays unpredictable. If the step
+        // overshoots nothing got burned so the slot is retired at the generation it's at, same as in `spawn`
+        let tombstone = match self.next_generation(generation) {
+            Some(next) if next != NonZeroU32::MAX => {
+                self.entities[index as usize].0 = next;
+                return;
+            }
+            Some(next) => next,
+            None => generation,
+        };
+        self.entities[index as usize] = (tombstone, EntityStatus::Tombstone);
+        self.tombstone_count += 1;
+        self.unfree(index);
+        self.observe_tombstone(index);
+    }
+
+    // Moves an alive entity to another index, e.g. to get a hot entity into a low index without compacting everything.
+    // The old handle is dead afterwards so it's up to the caller to move its components over to the returned one
+    pub(crate) fn relocate(
+        &mut self,
+        entity: Entity,
+        new_index: u32,
+    ) -> Result<Entity, RelocateError> {
+        if self.is_alive(entity) == false {
+            return Err(RelocateError::NotAlive);
+        }
+        self.flush();
+
+        let new_idx = new_index as usize;
+        let generation = match self.entities.get(new_idx) {
+            // Slots between the end and the new index are free for `spawn` to use later like in `spawn_at`
+            None => {
+                if new_idx >= u32::MAX as usize {
+                    panic!("Too many entities spawned in world");
+                }
+                for skipped in self.entities.len()..=new_idx {
+                    self.entities.push((NonZeroU32::MIN, EntityStatus::Dead));
+                    self.despawned.push_back(skipped as u32);
+                }
+                NonZeroU32::MIN
+            }
+            Some(&(_, EntityStatus::Alive)) => return Err(RelocateError::Occupied),
+            Some(&(_, EntityStatus::Tombstone)) => return Err(RelocateError::Tombstone),
+            // Bumped like `spawn` would so that old handles to this slot stay dead
+            Some(&(generation, EntityStatus::Dead)) => match self.next_generation(generation) {
+                Some(next) => next,
+                None => return Err(RelocateError::Tombstone),
+            },
+        };
+        self.unfree(new_index);
+        self.entities[new_idx] = (generation, EntityStatus::Alive);
+
+        // Same as `kill` except nothing actually got despawned so there's no despawn event
+        let (old_generation, old_status) = &mut self.entities[entity.index as usize];
+        match *old_generation == NonZeroU32::MAX {
+            true => {
+                *old_status = EntityStatus::Tombstone;
+                self.tombstone_count += 1;
+                self.observe_tombstone(entity.index);
+            }
+            false => {
+                *old_status = EntityStatus::Dead;
+                self.free(entity.index);
+            }
+        }
+        Ok(self.entity(new_index, generation))
+    }
+
+    // Returns how many of the entities were actually alive and got despawned, anything
+    // dead or from another world is skipped the same way `despawn` would
+    pub(crate) fn despawn_batch(&mut self, entities: &[Entity]) -> usize {
+        entities
+            .iter()
+            .filter(|&&entity| self.despawn(entity))
+            .count()
+    }
+
+    // Despawns every alive entity with an index in `range` and returns how many there were, for unloading a
+    // block of entities that were spawned together. Whatever part of the range is past the end is ignored
+    pub(crate) fn despawn_range(&mut self, range: core::ops::Range<u32>) -> usize {
+        self.flush();
+        let end = range.end.min(self.entities.len() as u32);
+        let mut count = 0;
+        for index in range.start..end {
+            if self.entities[index as usize].1 == EntityStatus::Alive {
+                self.kill(index);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Despawns every alive entity while keeping all of the allocations around. This goes through the
+    // same rules as `despawn` so slots keep their generation and get it bumped when they're reused,
+    // that way any entity handed out before the clear is still dead afterwards
+    pub(crate) fn clear(&mut self) {
+        self.flush();
+        for index in 0..self.entities.len() as u32 {
+            if self.entities[index as usize].1 == EntityStatus::Alive {
+                self.kill(index);
+            }
+        }
+    }
+
+    // Despawns every alive entity that `keep` returns false for, e.g. all the bullets that went off screen
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        self.flush();
+        for index in 0..self.entities.len() as u32 {
+            let (generation, status) = self.entities[index as usize];
+            if status == EntityStatus::Alive && keep(self.entity(index, generation)) == false {
+                self.kill(index);
+            }
+        }
+    }
+
+    // Spawns a fresh entity in this generator for every entity alive in `other` and returns which old entity
+    // became which new one, use it to move any components from `other`'s world over
+    #[cfg(feature = "std")]
+    pub(crate) fn absorb(&mut self, other: &EntityGenerator) -> HashMap<Entity, Entity> {
+        other
+            .iter_alive()
+            .map(|entity| (entity, self.spawn()))
+            .collect()
+    }
+
+    // Replaces this generator with a fresh one where all the alive entities are packed into the lowest
+    // indices, returns which old entity became which new one so component storage can be rewritten.
+    // The fresh generator gets a new world id so every handle from before the compaction is from another
+    // world as far as it's concerned, including for `StrictMode`, so using one panics under `Panic`. Every
+    // handle has to be swapped for its entry in the returned map before it's used again
+    // GUIDE: the world id is what stops old handles aliasing the renumbered entities, the packed slots start
+    // back at the first generation and the old index space past them is forgotten so there's no generation
+    // left to tell old and new apart by. Handles made with `from_bits` aren't tied to a world so they *can*
+    // alias the renumbered entities
+    #[cfg(feature = "std")]
+    pub(crate) fn compact(&mut self) -> HashMap<Entity, Entity> {
+        self.flush();
+        let mut compacted = EntityGenerator {
+            fifo: self.fifo,
+            strict_mode: self.strict_mode,
+            cooling: self.cooling.iter().map(|_| Vec::new()).collect(),
+            generation_strategy: self.generation_strategy,
+            rng: self.rng,
+            despawn_events: core::mem::take(&mut self.despawn_events),
+            record_despawns: self.record_despawns,
+            peak_alive: self.peak_alive,
+            ..EntityGenerator::with_capacity(self.alive)
+        };
+        let remap = compacted.absorb(self);
+        // Renumbering isn't really spawning anything so the observer only gets moved over afterwards
+        compacted.spawn_observer = self.spawn_observer.take();
+        compacted.tombstone_observer = self.tombstone_observer.take();
+        *self = compacted;
+        remap
+    }
+
+    // Puts a slot into whatever state a test needs while keeping the counts and free list in sync, so tests
+    // don't have to build generators by hand. Any slots skipped over to get to `index` are left dead
+    #[cfg(test)]
+    pub(crate) fn force_state(&mut self, index: u32, generation: u32, status: EntityStatus) {
+        self.flush();
+        while self.entities.len() <= index as usize {
+            self.despawned.push_back(self.entities.len() as u32);
+            self.entities.push((NonZeroU32::MIN, EntityStatus::Dead));
+        }
+
+        match self.entities[index as usize].1 {
+            EntityStatus::Alive => self.alive -= 1,
+            EntityStatus::Dead => self.unfree(index),
+            EntityStatus::Tombstone => self.tombstone_count -= 1,
+        }
+        match status {
+            EntityStatus::Alive => self.add_alive(1),
+            EntityStatus::Dead => self.despawned.push_back(index),
+            EntityStatus::Tombstone => self.tombstone_count += 1,
+        }
+        let generation = NonZeroU32::new(generation).expect("generations start at 1");
+        self.entities[index as usize] = (generation, status);
+    }
+
+    // Releases any spare capacity in the entities vec and the free list, liveness isn't affected
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.entities.shrink_to_fit();
+        self.despawned.shrink_to_fit();
+        for bucket in self.cooling.iter_mut() {
+            bucket.shrink_to_fit();
+        }
+    }
+
+    // Reserved entities are written into the snapshot as alive the same as `flush` would do
+    // GUIDE: the length of the recycle delay is saved but not which bucket each cooling slot is in, they all go
+    // on the free list behind everything that was already reusable so they still come back last. The observers
+    // can't be saved and neither are any despawn events that haven't been drained yet
+    pub(crate) fn snapshot(&self) -> GeneratorSnapshot {
+        let mut entities = self.entities.clone();
+        entities.resize(
+            entities.len() + self.reserved() as usize,
+            (NonZeroU32::MIN, EntityStatus::Alive),
+        );
+        // Oldest cooling slots first since they were closest to being reusable
+        let cooling = self.cooling.iter().flatten().copied();
+        let despawned = match self.fifo {
+            true => self.despawned.iter().copied().chain(cooling).collect(),
+            false => {
+                let mut despawned: Vec<_> = cooling.collect();
+                despawned.reverse();
+                despawned.extend(self.despawned.iter().copied());
+                despawned
+            }
+        };
+        GeneratorSnapshot {
+            entities,
+            despawned,
+            fifo: self.fifo,
+            world_id: self.world_id,
+            generation_strategy: self.generation_strategy,
+            rng: self.rng,
+            recycle_delay: self.cooling.len(),
+            strict_mode: self.strict_mode,
+            record_despawns: self.record_despawns,
+        }
+    }
+
+    // The restored generator keeps the saved world id so that handles from before the snapshot still work.
+    // Ids are only unique within a single run of the program though, so restoring a snapshot from an earlier
+    // run while some other generator already has the same id means the two will accept each other's entities
+    pub(crate) fn restore(snapshot: GeneratorSnapshot) -> Self {
+        let count = |wanted| {
+            snapshot
+                .entities
+                .iter()
+                .filter(|&&(_, status)| status == wanted)
+                .count()
+        };
+        let alive = count(EntityStatus::Alive);
+        Self {
+            alive,
+            // The real peak isn't saved so the best we can do is however many are alive now
+            peak_alive: alive as u32,
+            tombstone_count: count(EntityStatus::Tombstone) as u32,
+            entities: snapshot.entities,
+            despawned: snapshot.despawned.into(),
+            fifo: snapshot.fifo,
+            world_id: WorldId::reuse(snapshot.world_id),
+            generation_strategy: snapshot.generation_strategy,
+            rng: snapshot.rng,
+            strict_mode: snapshot.strict_mode,
+            record_despawns: snapshot.record_despawns,
+            ..Self::with_recycle_delay(snapshot.recycle_delay)
+        }
+    }
+}
+
+// Only yields alive entities, same as `iter_alive`. Boxed because there's no way to name the type `iter_alive` returns
+impl<'a> IntoIterator for &'a EntityGenerator {
+    type Item = Entity;
+    type IntoIter = Box<dyn Iterator<Item = Entity> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter_alive())
+    }
+}
+
+impl Default for EntityGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DespawnError, EntityGenerator, GeneratorStats, Liveness, RelocateError};
+    use super::{Entity, EntityStatus, WorldId};
+    use super::{GenStrategy, SlotStatus, SpawnAtError, StrictMode};
+    use std::num::NonZeroU32;
+
+    fn gen(generation: u32) -> NonZeroU32 {
+        NonZeroU32::new(generation).unwrap()
+    }
+
+    #[test]
+    fn spawn_one() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+
+        assert!(generator.is_alive(e1));
+
+        generator.despawn(e1);
+        assert!(generator.is_alive(e1) == false);
+    }
+
+    #[test]
+    fn other_world_entity() {
+        let mut generator_1 = EntityGenerator::new();
+        let mut generator_2 = EntityGenerator::new();
+
+        let e1_1 = generator_1.spawn();
+        assert!(generator_2.is_alive(e1_1) == false);
+        assert!(generator_2.despawn(e1_1) == false);
+
+        // GUIDE: maybe we should test that despawn call didnt mess with the next spawned entity
+        let e1_2 = generator_2.spawn();
+        assert_eq!(e1_1.to_bits(), e1_2.to_bits());
+        // They're the same handle as far as `==` goes, it's the generator that knows they aren't its own
+        assert_eq!(e1_1, e1_2);
+        assert!(generator_1.is_alive(e1_2) == false);
+    }
+
+    #[test]
+    fn other_world_colliding_index() {
+        let mut generator_1 = EntityGenerator::new();
+        let mut generator_2 = EntityGenerator::new();
+
+        let e1_1 = generator_1.spawn();
+        let e1_2 = generator_2.spawn();
+
+        // Same index and generation but world 2 still shouldnt think world 1's entity is alive
+        assert_eq!((e1_1.index(), e1_1.generation()), (0, 1));
+        assert_eq!((e1_2.index(), e1_2.generation()), (0, 1));
+        assert!(generator_2.is_alive(e1_1) == false);
+        assert_eq!(generator_2.liveness(e1_1), Liveness::ForeignWorld);
+        assert_eq!(generator_2.spawn_at(e1_1), Err(SpawnAtError::ForeignWorld));
+        assert_eq!(
+            generator_2.try_despawn(e1_1),
+            Err(DespawnError::ForeignWorld)
+        );
+        assert!(generator_2.is_alive(e1_2));
+
+        // Entities that arent tied to a world only get checked by index and generation
+        assert!(generator_2.is_alive(Entity::from_bits(e1_1.to_bits())));
+    }
+
+    #[test]
+    fn generation_reuse() {
+        let mut generator_1 = EntityGenerator::new();
+
+        let e1 = generator_1.spawn();
+        generator_1.despawn(e1);
+        let e2 = generator_1.spawn();
+
+        assert_eq!(
+            e2,
+            Entity {
+                index: 0,
+                generation: gen(2),
+                world: generator_1.world_id,
+            }
+        );
+    }
+
+    #[test]
+    fn tombstone() {
+        let mut generator_1 = EntityGenerator::new();
+        generator_1.force_state(0, u32::MAX, EntityStatus::Alive);
+
+        let e1 = Entity {
+            index: 0,
+            generation: NonZeroU32::MAX,
+            world: WorldId::NONE,
+        };
+
+        // GUIDE: lets just be really sure we manually created the entity correctly
+        assert!(generator_1.is_alive(e1));
+
+        generator_1.despawn(e1);
+        // GUIDE: probably dont need to but might aswell check that tombstone entities are considered dead
+        assert!(generator_1.is_alive(e1) == false);
+        assert_eq!(
+            generator_1.entities[0],
+            (NonZeroU32::MAX, EntityStatus::Tombstone)
+        );
+        assert_eq!(generator_1.tombstone_count(), 1);
+
+        let e2 = generator_1.spawn();
+        assert!(generator_1.is_alive(e2));
+        assert_eq!(
+            e2,
+            Entity {
+                index: 1,
+                generation: NonZeroU32::MIN,
+                world: generator_1.world_id,
+            }
+        );
+    }
+
+    #[test]
+    fn double_despawn() {
+        let mut generator_1 = EntityGenerator::new();
+        let e1 = generator_1.spawn();
+        assert!(generator_1.despawn(e1) == true);
+        assert!(generator_1.despawn(e1) == false);
+
+        // GUIDE: despawning pushes an entity to the Despawned vec... maybe we should check theres only one entry after calling that twice
+        assert!(generator_1.despawned.len() == 1);
+    }
+
+    #[test]
+    fn index_and_generation() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+        generator.despawn(e1);
+        let e3 = generator.spawn();
+
+        assert_eq!((e1.index(), e1.generation()), (0, 1));
+        assert_eq!((e2.index(), e2.generation()), (1, 1));
+        assert_eq!((e3.index(), e3.generation()), (0, 2));
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut generator = EntityGenerator::with_capacity(50_000);
+        let capacity = generator.entities.capacity();
+        assert!(capacity >= 50_000);
+
+        for _ in 0..50_000 {
+            generator.spawn();
+        }
+        assert_eq!(generator.entities.capacity(), capacity);
+    }
+
+    #[test]
+    fn alive_count() {
+        let mut generator = EntityGenerator::new();
+        assert_eq!(generator.alive_count(), 0);
+
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+        assert_eq!(generator.alive_count(), 2);
+
+        generator.despawn(e1);
+        generator.despawn(e1);
+        assert_eq!(generator.alive_count(), 1);
+
+        generator.spawn();
+        generator.despawn(e2);
+        assert_eq!(generator.alive_count(), 1);
+    }
+
+    #[test]
+    fn is_empty_and_capacity() {
+        let mut generator = EntityGenerator::new();
+        assert!(generator.is_empty());
+        assert_eq!(generator.capacity(), 0);
+
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+        assert!(generator.is_empty() == false);
+        assert_eq!(generator.capacity(), 2);
+
+        generator.despawn(e1);
+        generator.despawn(e2);
+        assert!(generator.is_empty());
+        assert_eq!(generator.capacity(), 2);
+    }
+
+    #[test]
+    fn iter_alive() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+        let e3 = generator.spawn();
+        generator.despawn(e2);
+
+        let alive: Vec<_> = generator.iter_alive().collect();
+        assert_eq!(alive, vec![e1, e3]);
+        assert!(alive.iter().all(|&e| generator.is_alive(e)));
+
+        // The recycled slot should come back with its bumped generation
+        let e4 = generator.spawn();
+        let alive: Vec<_> = generator.iter_alive().collect();
+        assert_eq!(alive, vec![e1, e4, e3]);
+        assert_eq!(alive[1].generation(), 2);
+    }
+
+    #[test]
+    fn default_is_empty() {
+        let generator = EntityGenerator::default();
+        assert!(generator.is_empty());
+        assert_eq!(generator.capacity(), 0);
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let entities = [
+            Entity {
+                index: 0,
+                generation: NonZeroU32::MIN,
+                world: WorldId::NONE,
+            },
+            Entity {
+                index: 23,
+                generation: gen(4),
+                world: WorldId::NONE,
+            },
+            Entity {
+                index: u32::MAX,
+                generation: NonZeroU32::MIN,
+                world: WorldId::NONE,
+            },
+            Entity {
+                index: 0,
+                generation: NonZeroU32::MAX,
+                world: WorldId::NONE,
+            },
+            Entity {
+                index: u32::MAX,
+                generation: NonZeroU32::MAX,
+                world: WorldId::NONE,
+            },
+        ];
+
+        for &e in entities.iter() {
+            assert_eq!(Entity::from_bits(e.to_bits()), e);
+        }
+        assert_eq!(entities[1].to_bits(), 4 << 32 | 23);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert!(generator.despawn(e1));
+        let e2 = generator.spawn();
+
+        let json = serde_json::to_string(&[e1, e2]).unwrap();
+        assert_eq!(json, format!("[{},{}]", e1.to_bits(), e2.to_bits()));
+        let loaded: [Entity; 2] = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, [e1, e2]);
+        assert!(generator.is_alive(loaded[0]) == false);
+        assert!(generator.is_alive(loaded[1]));
+
+        // Generation 0 never gets handed out, a save file containing one is broken
+        assert!(serde_json::from_str::<Entity>("7").is_err());
+    }
+
+    #[test]
+    fn sort_index_major() {
+        let e = |index, generation| Entity {
+            index,
+            generation: gen(generation),
+            world: WorldId::NONE,
+        };
+        let mut entities = vec![e(2, 1), e(0, 3), e(1, 2), e(0, 2), e(2, 5), e(1, 1)];
+        entities.sort();
+
+        assert_eq!(
+            entities,
+            vec![e(0, 2), e(0, 3), e(1, 1), e(1, 2), e(2, 1), e(2, 5)]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let e1 = Entity {
+            index: 23,
+            generation: gen(4),
+            world: WorldId::NONE,
+        };
+        assert_eq!(e1.to_string(), "23v4");
+    }
+
+    #[test]
+    fn spawn_batch() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(4);
+        assert_eq!(entities.len(), 4);
+        assert!(entities.iter().all(|&e| generator.is_alive(e)));
+
+        generator.despawn(entities[1]);
+        generator.despawn(entities[3]);
+
+        let batch = generator.spawn_batch(3);
+        assert_eq!(
+            batch,
+            vec![
+                Entity {
+                    index: 3,
+                    generation: gen(2),
+                    world: generator.world_id,
+                },
+                Entity {
+                    index: 1,
+                    generation: gen(2),
+                    world: generator.world_id,
+                },
+                Entity {
+                    index: 4,
+                    generation: NonZeroU32::MIN,
+                    world: generator.world_id,
+                },
+            ]
+        );
+        assert_eq!(generator.alive_count(), 5);
+
+        // Should hand out exactly what calling spawn 3 times would have
+        let mut generator_2 = EntityGenerator::new();
+        let entities = generator_2.spawn_batch(4);
+        generator_2.despawn(entities[1]);
+        generator_2.despawn(entities[3]);
+        let one_by_one: Vec<_> = (0..3).map(|_| generator_2.spawn().to_bits()).collect();
+        let batch: Vec<_> = batch.iter().map(|e| e.to_bits()).collect();
+        assert_eq!(batch, one_by_one);
+    }
+
+    #[test]
+    fn despawn_range() {
+        let mut generator = EntityGenerator::new();
+        generator.set_record_despawns(true);
+        let entities = generator.spawn_batch(10);
+        generator.despawn(entities[4]);
+        generator.force_state(5, u32::MAX, EntityStatus::Alive);
+
+        assert_eq!(generator.despawn_range(3..7), 3);
+        for (index, &e) in entities.iter().enumerate() {
+            assert_eq!(generator.is_alive(e), (3..7).contains(&index) == false);
+        }
+        assert_eq!(generator.tombstone_count(), 1);
+        assert_eq!(generator.drain_despawned().count(), 4);
+
+        assert_eq!(generator.despawn_range(8..100), 2);
+        assert_eq!(generator.despawn_range(50..100), 0);
+        assert_eq!(generator.alive_count(), 4);
+    }
+
+    #[test]
+    fn despawn_batch() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(4);
+        generator.despawn(entities[0]);
+
+        let foreign = Entity {
+            index: 10,
+            generation: NonZeroU32::MIN,
+            world: WorldId::NONE,
+        };
+        let count = generator.despawn_batch(&[entities[0], entities[1], foreign, entities[2]]);
+        assert_eq!(count, 2);
+        assert_eq!(generator.alive_count(), 1);
+        assert!(generator.is_alive(entities[3]));
+    }
+
+    #[test]
+    fn clear() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[1]);
+        let capacity = generator.entities.capacity();
+
+        generator.clear();
+        assert!(generator.is_empty());
+        assert!(entities.iter().all(|&e| generator.is_alive(e) == false));
+        assert_eq!(generator.despawned.len(), 3);
+        assert_eq!(generator.entities.capacity(), capacity);
+
+        // Reused slots have their generation bumped so old entities dont come back to life
+        let respawned = generator.spawn_batch(3);
+        assert!(respawned.iter().all(|e| e.generation() == 2));
+        assert!(entities.iter().all(|&e| generator.is_alive(e) == false));
+    }
+
+    #[test]
+    fn fifo_reuse() {
+        let mut generator = EntityGenerator::new_fifo();
+        let entities = generator.spawn_batch(3);
+        for &e in entities.iter() {
+            generator.despawn(e);
+        }
+
+        // Churning one entity at a time should cycle through every freed slot rather than hammering one
+        let mut reused = Vec::new();
+        for _ in 0..6 {
+            let e = generator.spawn();
+            reused.push(e.index());
+            generator.despawn(e);
+        }
+        assert_eq!(reused, vec![0, 1, 2, 0, 1, 2]);
+        assert!(generator
+            .entities
+            .iter()
+            .all(|&(generation, _)| generation == gen(3)));
+
+        // Whereas the default stack behaviour keeps reusing the same slot
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        for &e in entities.iter() {
+            generator.despawn(e);
+        }
+        for _ in 0..6 {
+            let e = generator.spawn();
+            assert_eq!(e.index(), 2);
+            generator.despawn(e);
+        }
+    }
+
+    #[test]
+    fn try_despawn() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert_eq!(generator.try_despawn(e1), Ok(()));
+        assert_eq!(generator.try_despawn(e1), Err(DespawnError::AlreadyDead));
+
+        let e2 = generator.spawn();
+        assert_eq!(e1.index(), e2.index());
+        assert_eq!(
+            generator.try_despawn(e1),
+            Err(DespawnError::StaleGeneration)
+        );
+        assert!(generator.is_alive(e2));
+
+        let foreign = Entity {
+            index: 1,
+            generation: NonZeroU32::MIN,
+            world: WorldId::NONE,
+        };
+        assert_eq!(
+            generator.try_despawn(foreign),
+            Err(DespawnError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn liveness() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert_eq!(generator.liveness(e1), Liveness::Alive);
+
+        generator.despawn(e1);
+        assert_eq!(generator.liveness(e1), Liveness::Despawned);
+
+        let e2 = generator.spawn();
+        assert_eq!(generator.liveness(e1), Liveness::StaleGeneration);
+        assert_eq!(generator.liveness(e2), Liveness::Alive);
+
+        let foreign = Entity {
+            index: 1,
+            generation: NonZeroU32::MIN,
+            world: WorldId::NONE,
+        };
+        assert_eq!(generator.liveness(foreign), Liveness::OutOfRange);
+    }
+
+    #[test]
+    fn lost_capacity() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(4);
+        for &index in [1, 3].iter() {
+            generator.force_state(index, u32::MAX, EntityStatus::Alive);
+        }
+        assert_eq!(generator.lost_capacity(), 0);
+
+        for &e in generator.iter_alive().collect::<Vec<_>>().iter() {
+            generator.despawn(e);
+        }
+        assert_eq!(generator.lost_capacity(), 2);
+        assert!(entities.iter().all(|&e| generator.is_alive(e) == false));
+        assert_eq!(generator.reusable_slots(), 2);
+    }
+
+    #[test]
+    fn tombstone_count() {
+        let mut generator = EntityGenerator::new();
+        generator.force_state(0, u32::MAX - 1, EntityStatus::Dead);
+        assert_eq!(generator.tombstone_count(), 0);
+
+        let e1 = generator.spawn();
+        assert_eq!(e1.generation(), u32::MAX);
+        generator.despawn(e1);
+        assert_eq!(generator.tombstone_count(), 1);
+
+        // Clearing goes through the same tombstone rule
+        generator.force_state(1, u32::MAX, EntityStatus::Alive);
+        generator.clear();
+        assert_eq!(generator.tombstone_count(), 2);
+    }
+
+    #[test]
+    fn spawn_at() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(5);
+        generator.despawn(entities[1]);
+        generator.despawn(entities[3]);
+        let respawned = generator.spawn();
+        // Entities get written out to the save as bits
+        let recorded: Vec<_> = entities
+            .iter()
+            .chain(Some(&respawned))
+            .map(|e| e.to_bits())
+            .collect();
+        let saved: Vec<_> = recorded
+            .iter()
+            .map(|&bits| generator.is_alive(Entity::from_bits(bits)))
+            .collect();
+        let saved_alive: Vec<_> = generator.iter_alive().map(|e| e.to_bits()).collect();
+
+        let replay = |generator: &mut EntityGenerator| {
+            for &bits in saved_alive.iter() {
+                assert_eq!(generator.spawn_at(Entity::from_bits(bits)), Ok(()));
+            }
+            let liveness: Vec<_> = recorded
+                .iter()
+                .map(|&bits| generator.is_alive(Entity::from_bits(bits)))
+                .collect();
+            assert_eq!(liveness, saved);
+            assert_eq!(generator.alive_count(), saved_alive.len());
+
+            // Newly spawned entities shouldnt collide with anything we restored
+            let e = generator.spawn();
+            assert!(saved_alive.contains(&e.to_bits()) == false);
+        };
+
+        generator.clear();
+        replay(&mut generator);
+        replay(&mut EntityGenerator::new());
+    }
+
+    #[test]
+    fn spawn_at_conflicts() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert_eq!(generator.spawn_at(e1), Ok(()));
+        assert_eq!(generator.alive_count(), 1);
+
+        let conflicting = Entity {
+            index: 0,
+            generation: gen(3),
+            world: WorldId::NONE,
+        };
+        assert_eq!(
+            generator.spawn_at(conflicting),
+            Err(SpawnAtError::AlreadyAlive)
+        );
+
+        generator.force_state(1, u32::MAX, EntityStatus::Tombstone);
+        let tombstoned = Entity {
+            index: 1,
+            generation: gen(7),
+            world: WorldId::NONE,
+        };
+        assert_eq!(generator.spawn_at(tombstoned), Err(SpawnAtError::Tombstone));
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut generator = EntityGenerator::with_capacity(1000);
+        let entities = generator.spawn_batch(100);
+        generator.despawn_batch(&entities[..50]);
+        generator.spawn_batch(50);
+        assert!(generator.entities.capacity() >= 1000);
+        assert!(generator.despawned.capacity() >= 50);
+
+        generator.shrink_to_fit();
+        assert_eq!(generator.entities.capacity(), 100);
+        assert!(generator.despawned.capacity() < 50);
+        assert!(entities[50..].iter().all(|&e| generator.is_alive(e)));
+        assert_eq!(generator.alive_count(), 100);
+    }
+
+    #[test]
+    fn reserve_entity() {
+        fn reserve_two(generator: &EntityGenerator) -> (Entity, Entity) {
+            (generator.reserve_entity(), generator.reserve_entity())
+        }
+
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        let (e2, e3) = reserve_two(&generator);
+        assert_eq!((e2.index(), e3.index()), (1, 2));
+        assert!(generator.is_alive(e2) && generator.is_alive(e3));
+        assert_eq!(generator.liveness(e3), Liveness::Alive);
+        assert_eq!(generator.alive_count(), 3);
+        assert_eq!(generator.capacity(), 1);
+        assert_eq!(generator.iter_alive().collect::<Vec<_>>(), vec![e1, e2, e3]);
+
+        generator.flush();
+        assert_eq!(generator.capacity(), 3);
+        assert_eq!(generator.alive_count(), 3);
+        assert!(generator.is_alive(e2) && generator.is_alive(e3));
+
+        // Spawning flushes first so it cant hand out an index that's already been reserved
+        let e4 = generator.reserve_entity();
+        let e5 = generator.spawn();
+        assert_eq!((e4.index(), e5.index()), (3, 4));
+        assert!(generator.despawn(e4));
+        assert!(generator.is_alive(e4) == false);
+    }
+
+    #[test]
+    fn reserve_entities_across_threads() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn();
+
+        let (a, b) = std::thread::scope(|scope| {
+            let a = scope.spawn(|| generator.reserve_entities(1000).collect::<Vec<_>>());
+            let b = scope.spawn(|| generator.reserve_entities(1000).collect::<Vec<_>>());
+            (a.join().unwrap(), b.join().unwrap())
+        });
+
+        let mut indices: Vec<_> = a.iter().chain(b.iter()).map(|e| e.index()).collect();
+        indices.sort();
+        indices.dedup();
+        assert_eq!(indices, (1..2001).collect::<Vec<_>>());
+
+        generator.flush();
+        assert_eq!(generator.alive_count(), 2001);
+        assert!(a.iter().chain(b.iter()).all(|&e| generator.is_alive(e)));
+    }
+
+    #[test]
+    fn reserve_entities_outlives_later_reservations() {
+        let generator = EntityGenerator::new();
+        let first = generator.reserve_entities(3);
+        let second: Vec<_> = generator.reserve_entities(2).collect();
+        let first: Vec<_> = first.collect();
+
+        assert_eq!(
+            first.iter().map(|e| e.index()).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            second.iter().map(|e| e.index()).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut generator = EntityGenerator::new();
+        let mut entities = generator.spawn_batch(10);
+        for round in 0..5 {
+            let dead: Vec<_> = entities.iter().copied().step_by(2).collect();
+            generator.despawn_batch(&dead);
+            entities.retain(|&e| generator.is_alive(e));
+            entities.extend(generator.spawn_batch(round));
+        }
+        let len = generator.capacity() as u32;
+        generator.force_state(len, u32::MAX, EntityStatus::Tombstone);
+        let reserved = generator.reserve_entity();
+
+        let snapshot = generator.snapshot();
+        let saved: Vec<_> = generator.iter_alive().map(|e| e.to_bits()).collect();
+        let mut restored = EntityGenerator::restore(snapshot.clone());
+
+        let alive: Vec<_> = restored.iter_alive().map(|e| e.to_bits()).collect();
+        assert_eq!(alive, saved);
+        assert!(alive.contains(&reserved.to_bits()));
+        // Handles from the original generator are still good, not just ones that went through `to_bits`
+        assert!(entities.iter().all(|&e| restored.is_alive(e)));
+        assert!(restored.is_alive(reserved));
+        assert!(EntityGenerator::new().is_alive(entities[0]) == false);
+        assert_eq!(restored.alive_count(), generator.alive_count());
+        assert_eq!(restored.tombstone_count(), 1);
+        assert_eq!(restored.snapshot(), snapshot);
+
+        // Both generators should carry on handing out the same entities
+        for _ in 0..10 {
+            let e = restored.spawn();
+            assert!(saved.contains(&e.to_bits()) == false);
+            assert_eq!(e.to_bits(), generator.spawn().to_bits());
+        }
+    }
+
+    #[test]
+    fn snapshot_restore_random_step() {
+        let strategy = GenStrategy::RandomStep { seed: 7 };
+        let mut generator = EntityGenerator::with_generation_strategy(strategy);
+        let entities = generator.spawn_batch(4);
+        generator.despawn_batch(&entities);
+        generator.spawn_batch(2);
+
+        let mut restored = EntityGenerator::restore(generator.snapshot());
+        for e in generator
+            .spawn_batch(2)
+            .into_iter()
+            .chain(generator.spawn_batch(3))
+        {
+            assert_eq!(restored.spawn().to_bits(), e.to_bits());
+        }
+        let e = generator.iter_alive().next().unwrap();
+        generator.despawn(e);
+        restored.despawn(e);
+        assert_eq!(restored.spawn().to_bits(), generator.spawn().to_bits());
+    }
+
+    #[test]
+    fn snapshot_restore_recycle_delay() {
+        for fifo in [false, true] {
+            let mut generator = EntityGenerator {
+                fifo,
+                ..EntityGenerator::with_recycle_delay(2)
+            };
+            generator.set_strict_mode(StrictMode::Silent);
+            generator.set_record_despawns(true);
+            let entities = generator.spawn_batch(6);
+            generator.despawn_batch(&entities[..2]);
+            generator.spawn_batch(2);
+            generator.despawn(entities[2]);
+            assert_eq!(generator.reusable_slots(), 2);
+
+            let mut restored = EntityGenerator::restore(generator.snapshot());
+            assert_eq!(restored.cooling.len(), 2);
+            assert_eq!(restored.strict_mode, StrictMode::Silent);
+            assert!(restored.record_despawns);
+            assert_eq!(restored.snapshot(), generator.snapshot());
+
+            // The slot that was still cooling down is reused after the ones that were already reusable
+            let mut indices: Vec<_> = restored.spawn_batch(3).iter().map(|e| e.index()).collect();
+            assert_eq!(indices.pop(), Some(2));
+            indices.sort_unstable();
+            assert_eq!(indices, vec![0, 1]);
+        }
+    }
+
+    #[test]
+    fn option_entity_niche() {
+        assert_eq!(
+            std::mem::size_of::<Option<Entity>>(),
+            std::mem::size_of::<Entity>()
+        );
+    }
+
+    #[test]
+    fn entity_at_index() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[1]);
+        let e4 = generator.spawn();
+        generator.despawn(entities[2]);
+        let reserved = generator.reserve_entity();
+
+        assert_eq!(generator.entity_at_index(0), Some(entities[0]));
+        assert_eq!(generator.entity_at_index(1), Some(e4));
+        assert_eq!(generator.entity_at_index(2), None);
+        assert_eq!(generator.entity_at_index(3), Some(reserved));
+        assert_eq!(generator.entity_at_index(4), None);
+        assert_eq!(generator.entity_at_index(u32::MAX), None);
+    }
+
+    #[test]
+    fn placeholder() {
+        let mut generator = EntityGenerator::new();
+        assert!(generator.is_alive(Entity::PLACEHOLDER) == false);
+        generator.spawn_batch(10);
+        generator.reserve_entity();
+        assert!(generator.is_alive(Entity::PLACEHOLDER) == false);
+        assert!(generator.despawn(Entity::PLACEHOLDER) == false);
+        assert_eq!(generator.alive_count(), 11);
+    }
+
+    #[test]
+    fn raw_parts() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[1]);
+
+        for &e in entities.iter() {
+            let raw = Entity::from_raw_parts(e.index(), e.generation());
+            assert_eq!((raw.index(), raw.generation()), (e.index(), e.generation()));
+            assert_eq!(raw.to_bits(), e.to_bits());
+            assert_eq!(generator.is_alive(raw), generator.is_alive(e));
+        }
+        assert!(generator.is_alive(Entity::from_raw_parts(0, 7)) == false);
+        assert!(generator.is_alive(Entity::from_raw_parts(23, 1)) == false);
+        assert_eq!(std::mem::size_of::<Entity>(), 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_raw_parts_generation_zero() {
+        Entity::from_raw_parts(0, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_bits_generation_zero() {
+        Entity::from_bits(23);
+    }
+
+    #[test]
+    fn try_from_bits() {
+        assert_eq!(Entity::try_from_bits(23), None);
+        assert_eq!(Entity::try_from_bits(u32::MAX as u64), None);
+        let e = Entity::try_from_bits(2 << 32 | 23).unwrap();
+        assert_eq!((e.index(), e.generation()), (23, 2));
+        assert_eq!(
+            Entity::try_from_bits(e.to_bits()),
+            Some(Entity::from_bits(e.to_bits()))
+        );
+    }
+
+    #[test]
+    fn retain() {
+        let mut generator = EntityGenerator::new();
+        let mut entities = generator.spawn_batch(6);
+        generator.force_state(1, u32::MAX, EntityStatus::Alive);
+        entities[1] = generator.iter_alive().nth(1).unwrap();
+        generator.despawn(entities[2]);
+
+        let mut seen = Vec::new();
+        generator.retain(|e| {
+            seen.push(e);
+            e.index() % 2 == 0
+        });
+        assert_eq!(
+            seen,
+            vec![
+                entities[0],
+                entities[1],
+                entities[3],
+                entities[4],
+                entities[5]
+            ]
+        );
+
+        let alive: Vec<_> = generator.iter_alive().collect();
+        assert_eq!(alive, vec![entities[0], entities[4]]);
+        assert!(generator.is_alive(entities[1]) == false);
+        assert!(generator.is_alive(entities[3]) == false);
+        assert!(generator.is_alive(entities[5]) == false);
+
+        // Slot 1 was at the last generation so it should have been tombstoned rather than freed
+        assert_eq!(generator.tombstone_count(), 1);
+        assert!(generator.despawned.contains(&1) == false);
+    }
+
+    #[test]
+    fn filter_alive() {
+        let mut generator = EntityGenerator::new();
+        let mut entities = generator.spawn_batch(4);
+        generator.despawn(entities[0]);
+        generator.despawn(entities[2]);
+        entities.push(generator.spawn());
+        entities.push(EntityGenerator::new().spawn());
+
+        let alive: Vec<_> = generator.filter_alive(entities.iter().copied()).collect();
+        assert_eq!(alive, vec![entities[1], entities[3], entities[4]]);
+    }
+
+    #[test]
+    fn get_or_spawn() {
+        let mut generator = EntityGenerator::new();
+        let e1 = Entity::from_bits(3 << 32 | 2);
+        assert_eq!(generator.get_or_spawn(e1), Ok(true));
+        assert_eq!(generator.get_or_spawn(e1), Ok(false));
+        assert!(generator.is_alive(e1));
+        assert_eq!(generator.alive_count(), 1);
+        assert_eq!(generator.capacity(), 3);
+
+        let e2 = generator.spawn();
+        assert_eq!(generator.get_or_spawn(e2), Ok(false));
+        assert_eq!(generator.alive_count(), 2);
+    }
+
+    #[test]
+    fn get_or_spawn_conflict() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn();
+        assert_eq!(
+            generator.get_or_spawn(Entity::from_bits(3 << 32)),
+            Err(SpawnAtError::AlreadyAlive)
+        );
+        assert_eq!(generator.alive_count(), 1);
+    }
+
+    #[test]
+    fn generation_overflow_skips_slot() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn();
+        generator.force_state(0, u32::MAX - 2, EntityStatus::Alive);
+        let mut e = generator.iter_alive().next().unwrap();
+        while e.index() == 0 {
+            assert!(generator.despawn(e));
+            e = generator.spawn();
+        }
+        assert_eq!(
+            generator.entities[0],
+            (NonZeroU32::MAX, EntityStatus::Tombstone)
+        );
+        assert_eq!((e.index(), e.generation()), (1, 1));
+
+        // A maxed out slot that somehow got onto the free list gets retired instead of handed out
+        generator.force_state(2, u32::MAX, EntityStatus::Dead);
+        let e = generator.spawn();
+        assert_eq!((e.index(), e.generation()), (3, 1));
+        assert_eq!(
+            generator.entities[2],
+            (NonZeroU32::MAX, EntityStatus::Tombstone)
+        );
+        assert_eq!(generator.tombstone_count(), 2);
+        assert!(generator.despawned.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn absorb() {
+        let mut master = EntityGenerator::new();
+        let existing = master.spawn_batch(2);
+
+        let mut sub = EntityGenerator::new();
+        let entities = sub.spawn_batch(3);
+        sub.despawn(entities[1]);
+
+        let remap = master.absorb(&sub);
+        assert_eq!(remap.len(), 2);
+        assert!(remap.contains_key(&entities[1]) == false);
+        for &old in [entities[0], entities[2]].iter() {
+            let new = remap[&old];
+            assert!(master.is_alive(new));
+            assert!(existing.contains(&new) == false);
+        }
+        assert_ne!(remap[&entities[0]], remap[&entities[2]]);
+        assert_eq!(master.alive_count(), 4);
+    }
+
+    #[test]
+    fn slots() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[0]);
+        generator.force_state(2, u32::MAX, EntityStatus::Tombstone);
+        generator.reserve_entity();
+
+        let slots: Vec<_> = generator.slots().collect();
+        assert_eq!(
+            slots,
+            vec![
+                (0, 1, SlotStatus::Dead),
+                (1, 1, SlotStatus::Alive),
+                (2, u32::MAX, SlotStatus::Tombstone),
+            ]
+        );
+    }
+
+    #[test]
+    fn fragmentation_ratio() {
+        let mut generator = EntityGenerator::new();
+        assert_eq!(generator.fragmentation_ratio(), 0.0);
+
+        let entities = generator.spawn_batch(8);
+        assert_eq!(generator.fragmentation_ratio(), 0.0);
+
+        generator.despawn_batch(&entities[..2]);
+        assert_eq!(generator.fragmentation_ratio(), 0.25);
+
+        generator.despawn_batch(&entities[2..]);
+        assert_eq!(generator.fragmentation_ratio(), 1.0);
+
+        generator.spawn_batch(4);
+        assert_eq!(generator.fragmentation_ratio(), 0.5);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn compact() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(10);
+        let kept = [entities[1], entities[4], entities[5], entities[9]];
+        generator.retain(|e| kept.contains(&e));
+        generator.force_state(10, u32::MAX, EntityStatus::Tombstone);
+
+        let remap = generator.compact();
+        assert_eq!(remap.len(), kept.len());
+        assert_eq!(generator.capacity(), kept.len());
+        assert_eq!(generator.fragmentation_ratio(), 0.0);
+        assert_eq!(generator.tombstone_count(), 0);
+        assert!(generator
+            .slots()
+            .all(|(_, _, status)| status == SlotStatus::Alive));
+
+        for &old in kept.iter() {
+            assert!(generator.is_alive(remap[&old]));
+            assert!(generator.is_alive(old) == false);
+        }
+        let mut indices: Vec<_> = remap.values().map(|e| e.index()).collect();
+        indices.sort();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "from another world")]
+    fn compact_strict_mode() {
+        let mut generator = EntityGenerator::new();
+        generator.set_strict_mode(StrictMode::Panic);
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[0]);
+
+        let remap = generator.compact();
+        assert!(generator.is_alive(remap[&entities[1]]));
+        generator.is_alive(entities[1]);
+    }
+
+    #[test]
+    fn drain_despawned() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(5);
+        generator.despawn(entities[2]);
+        assert_eq!(generator.drain_despawned().count(), 0);
+
+        generator.set_record_despawns(true);
+        generator.despawn(entities[3]);
+        generator.despawn(entities[0]);
+        generator.despawn(entities[0]);
+        generator.despawn(EntityGenerator::new().spawn());
+        generator.retain(|e| e != entities[1]);
+
+        let drained: Vec<_> = generator.drain_despawned().collect();
+        assert_eq!(drained, vec![entities[3], entities[0], entities[1]]);
+        assert_eq!(generator.drain_despawned().count(), 0);
+
+        // Turning it off drops whatever was still waiting
+        generator.despawn(entities[4]);
+        generator.set_record_despawns(false);
+        generator.set_record_despawns(true);
+        assert_eq!(generator.drain_despawned().count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn spawn_observer() {
+        use std::sync::{Arc, Mutex};
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let mut generator = EntityGenerator::new();
+        let log = observed.clone();
+        generator.set_spawn_observer(move |e| log.lock().unwrap().push(e));
+
+        let mut spawned = generator.spawn_batch(3);
+        generator.despawn(spawned[1]);
+        spawned.push(generator.spawn());
+        spawned.push(generator.spawn());
+        generator.despawn(spawned[0]);
+        spawned.extend(generator.spawn_batch(2));
+
+        assert_eq!(*observed.lock().unwrap(), spawned);
+        assert_eq!(spawned.len(), 7);
+
+        generator.compact();
+        assert_eq!(observed.lock().unwrap().len(), 7);
+        generator.spawn();
+        assert_eq!(observed.lock().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn tombstone_observer() {
+        use std::sync::{Arc, Mutex};
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let mut generator = EntityGenerator::new();
+        let log = observed.clone();
+        generator.set_tombstone_observer(move |index| log.lock().unwrap().push(index));
+
+        let entities = generator.spawn_batch(3);
+        generator.despawn(entities[0]);
+        assert!(observed.lock().unwrap().is_empty());
+
+        // Most recently freed so it's the slot that gets reused next
+        generator.force_state(2, u32::MAX - 1, EntityStatus::Dead);
+        let e = generator.spawn();
+        assert_eq!((e.index(), e.generation()), (2, u32::MAX));
+        generator.despawn(e);
+        assert_eq!(*observed.lock().unwrap(), vec![2]);
+        assert_eq!(generator.tombstone_count(), 1);
+
+        // Retired on spawn because the next generation would overflow
+        generator.force_state(0, u32::MAX, EntityStatus::Dead);
+        assert_eq!(generator.spawn().index(), 3);
+        // Burnt up to the last generation
+        generator.force_state(4, u32::MAX - 1, EntityStatus::Dead);
+        generator.burn_generation(4);
+        // Moved away from a slot at the last generation
+        generator.force_state(5, u32::MAX, EntityStatus::Alive);
+        let e = generator.entity_at_index(5).unwrap();
+        generator.relocate(e, 10).unwrap();
+
+        assert_eq!(*observed.lock().unwrap(), vec![2, 0, 4, 5]);
+        assert_eq!(generator.tombstone_count(), 4);
+    }
+
+    fn foreign_entity() -> Entity {
+        EntityGenerator::new().spawn()
+    }
+
+    #[test]
+    fn strict_mode_silent() {
+        let generator = EntityGenerator::new();
+        assert!(generator.is_alive(foreign_entity()) == false);
+        assert!(generator.warned.load(std::sync::atomic::Ordering::Relaxed) == false);
+    }
+
+    #[test]
+    fn strict_mode_warn() {
+        let mut generator = EntityGenerator::new();
+        generator.set_strict_mode(StrictMode::Warn);
+        assert!(generator.is_alive(foreign_entity()) == false);
+        assert!(generator.warned.load(std::sync::atomic::Ordering::Relaxed));
+        // Only the first one is printed but the answer is the same every time
+        assert!(generator.is_alive(foreign_entity()) == false);
+        assert!(generator.despawn(foreign_entity()) == false);
+    }
+
+    #[test]
+    #[should_panic(expected = "from another world")]
+    fn strict_mode_panic() {
+        let mut generator = EntityGenerator::new();
+        generator.set_strict_mode(StrictMode::Panic);
+        generator.is_alive(foreign_entity());
+    }
+
+    #[test]
+    fn recycle_delay() {
+        let mut generator = EntityGenerator::with_recycle_delay(3);
+        let entities = generator.spawn_batch(2);
+        generator.despawn(entities[0]);
+        assert_eq!(generator.reusable_slots(), 0);
+
+        // The freed slot sits out the next three spawns so the old handle can't be mistaken for a new one
+        for index in 2..5 {
+            assert_eq!(generator.spawn().index(), index);
+            assert!(generator.is_alive(entities[0]) == false);
+        }
+        let reused = generator.spawn();
+        assert_eq!((reused.index(), reused.generation()), (0, 2));
+
+        // Same thing through `spawn_batch`
+        generator.despawn(entities[1]);
+        let batch = generator.spawn_batch(4);
+        let indices: Vec<_> = batch.iter().map(|e| e.index()).collect();
+        assert_eq!(indices, vec![5, 6, 7, 1]);
+    }
+
+    #[test]
+    fn free_list() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(5);
+        generator.despawn(entities[3]);
+        generator.despawn(entities[1]);
+        assert_eq!(generator.free_list(), &[3, 1]);
+        assert_eq!(generator.spawn().index(), 1);
+        assert_eq!(generator.free_list(), &[3]);
+
+        let mut generator = EntityGenerator::new_fifo();
+        let entities = generator.spawn_batch(5);
+        generator.despawn(entities[3]);
+        generator.despawn(entities[1]);
+        assert_eq!(generator.free_list(), &[3, 1]);
+        assert_eq!(generator.spawn().index(), 3);
+        // Popping off the front can leave the `VecDeque` wrapped around
+        generator.despawn(entities[0]);
+        generator.despawn(entities[2]);
+        assert_eq!(generator.free_list(), &[1, 0, 2]);
+
+        let mut generator = EntityGenerator::with_recycle_delay(2);
+        let entities = generator.spawn_batch(5);
+        generator.despawn(entities[3]);
+        generator.spawn();
+        generator.despawn(entities[1]);
+        generator.despawn(entities[4]);
+        assert!(generator.free_list().is_empty());
+        let cooling: Vec<_> = generator.cooling_slots().collect();
+        assert_eq!(cooling, vec![&[3][..], &[1, 4][..]]);
+    }
+
+    #[test]
+    fn reusable_slots() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(10);
+        assert_eq!(generator.reusable_slots(), 0);
+
+        generator.despawn_batch(&entities[..4]);
+        assert_eq!(generator.reusable_slots(), 4);
+
+        generator.spawn();
+        assert_eq!(generator.reusable_slots(), 3);
+    }
+
+    #[test]
+    fn despawn_checks_generation() {
+        let mut generator = EntityGenerator::new();
+        let old = generator.spawn();
+        generator.despawn(old);
+        let current = generator.spawn();
+        assert_eq!(current.index(), old.index());
+
+        // Neither an old handle or one from the future should be able to despawn whoever is in the slot now
+        let forged = Entity {
+            generation: gen(5),
+            ..current
+        };
+        assert!(generator.despawn(old) == false);
+        assert!(generator.despawn(forged) == false);
+        assert!(generator.is_alive(current));
+        assert_eq!(generator.alive_count(), 1);
+    }
+
+    #[test]
+    fn stats() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(8);
+        generator.despawn_batch(&entities[..5]);
+        generator.spawn();
+        generator.force_state(8, u32::MAX, EntityStatus::Alive);
+        generator.despawn(Entity::from_bits(u64::MAX << 32 | 8));
+        generator.reserve_entity();
+
+        assert_eq!(
+            generator.stats(),
+            GeneratorStats {
+                alive: 5,
+                dead_reusable: 4,
+                tombstoned: 1,
+                capacity: 9,
+                high_water_mark: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn high_water_mark() {
+        let mut generator = EntityGenerator::new();
+        let entities: Vec<_> = (0..10).map(|_| generator.spawn()).collect();
+        assert_eq!(generator.high_water_mark(), 10);
+
+        generator.despawn_batch(&entities[..5]);
+        assert_eq!(generator.high_water_mark(), 10);
+
+        generator.spawn_batch(3);
+        assert_eq!(generator.high_water_mark(), 10);
+        generator.spawn_batch(4);
+        assert_eq!(generator.high_water_mark(), 12);
+
+        generator.clear();
+        let _reserved = generator.reserve_entities(20);
+        generator.flush();
+        assert_eq!(generator.high_water_mark(), 20);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn high_water_mark_compact() {
+        let mut generator = EntityGenerator::new();
+        let entities: Vec<_> = (0..20).map(|_| generator.spawn()).collect();
+        generator.despawn_batch(&entities[5..]);
+
+        // Compacting moves entities around but the peak is still the peak
+        generator.compact();
+        assert_eq!(generator.high_water_mark(), 20);
+        assert_eq!(generator.alive_count(), 5);
+    }
+
+    #[test]
+    fn deterministic() {
+        // Tiny xorshift so we get the same "random" ops every run
+        fn op_log(seed: u64, len: usize) -> Vec<u64> {
+            let mut state = seed;
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                })
+                .collect()
+        }
+
+        fn replay(mut generator: EntityGenerator, log: &[u64]) -> Vec<u64> {
+            let mut handed_out = Vec::new();
+            let mut alive = Vec::new();
+            for &op in log.iter() {
+                let arg = (op >> 8) as usize;
+                match op % 8 {
+                    0..=2 => alive.push(generator.spawn()),
+                    3 => alive.extend(generator.spawn_batch(arg % 5)),
+                    4 | 5 if alive.is_empty() == false => {
+                        generator.despawn(alive.swap_remove(arg % alive.len()));
+                    }
+                    6 => {
+                        let victims: Vec<_> = alive.iter().copied().step_by(3).collect();
+                        generator.despawn_batch(&victims);
+                        alive.retain(|&e| generator.is_alive(e));
+                    }
+                    7 if arg % 10 == 3 => {
+                        generator.clear();
+                        alive.clear();
+                    }
+                    _ => (),
+                }
+                handed_out.extend(alive.iter().map(|e| e.to_bits()));
+            }
+            handed_out
+        }
+
+        let log = op_log(0x5eed, 2000);
+        let lifo = replay(EntityGenerator::new(), &log);
+        assert_eq!(lifo, replay(EntityGenerator::new(), &log));
+        let fifo = replay(EntityGenerator::new_fifo(), &log);
+        assert_eq!(fifo, replay(EntityGenerator::new_fifo(), &log));
+        assert_ne!(lifo, fifo);
+    }
+
+    #[test]
+    fn alive_bitset() {
+        let mut generator = EntityGenerator::new();
+        assert!(generator.alive_bitset().is_empty());
+
+        let entities = generator.spawn_batch(130);
+        generator.retain(|e| e.index() % 3 == 0 && e.index() != 63);
+        let reserved = generator.reserve_entity();
+
+        let bits = generator.alive_bitset();
+        assert_eq!(bits.len(), 3);
+        for index in 0..bits.len() as u32 * 64 {
+            let set = bits[index as usize / 64] & 1 << (index % 64) != 0;
+            let alive = match entities.get(index as usize) {
+                Some(&e) => generator.is_alive(e),
+                None => index == reserved.index(),
+            };
+            assert_eq!(set, alive, "{}", index);
+        }
+    }
+
+    #[test]
+    fn book_ids() {
+        for &id in [0, 1, 23, u32::MAX as u64].iter() {
+            let e = Entity::from_book_id(id).unwrap();
+            assert_eq!((e.index() as u64, e.generation()), (id, 1));
+            assert_eq!(e.to_book_id(), Some(id));
+            assert_eq!(Entity::from_bits(e.to_bits()), e);
+        }
+        assert_eq!(Entity::from_book_id(u32::MAX as u64 + 1), None);
+        assert_eq!(Entity::from_book_id(u64::MAX), None);
+
+        // Book saves load into a fresh generator the same way any other unbound entity would
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert_eq!(e1.to_book_id(), Some(0));
+        assert!(generator.is_alive(Entity::from_book_id(0).unwrap()));
+        generator.despawn(e1);
+        assert_eq!(generator.spawn().to_book_id(), None);
+    }
+
+    #[test]
+    fn burn_generation() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(2);
+        generator.despawn(entities[0]);
+
+        generator.burn_generation(0);
+        generator.burn_generation(0);
+        let e = generator.spawn();
+        assert_eq!((e.index(), e.generation()), (0, 4));
+        assert!(generator.is_alive(entities[0]) == false);
+
+        // Slot 1 would be spawned at the last generation next so burning it retires it
+        generator.despawn(entities[1]);
+        generator.force_state(1, u32::MAX - 2, EntityStatus::Dead);
+        generator.burn_generation(1);
+        assert_eq!(generator.reusable_slots(), 1);
+        generator.burn_generation(1);
+        assert_eq!(
+            generator.entities[1],
+            (NonZeroU32::MAX, EntityStatus::Tombstone)
+        );
+        assert_eq!(generator.reusable_slots(), 0);
+        assert_eq!(generator.spawn().index(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn burn_generation_alive() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn();
+        generator.burn_generation(0);
+    }
+
+    #[test]
+    fn assert_alive() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        assert_eq!(generator.assert_alive(e1), e1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to use despawned entity 0v1")]
+    fn assert_alive_despawned() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        generator.despawn(e1);
+        generator.assert_alive(e1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to use entity 0v1 but its slot has been reused since")]
+    fn assert_alive_stale() {
+        let mut generator = EntityGenerator::new();
+        let e1 = generator.spawn();
+        generator.despawn(e1);
+        generator.spawn();
+        generator.assert_alive(e1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to use entity 5v1 but its index was never spawned")]
+    fn assert_alive_out_of_range() {
+        let generator = EntityGenerator::new();
+        generator.assert_alive(Entity::from_bits(1 << 32 | 5));
+    }
+
+    #[test]
+    fn force_state() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn();
+        generator.force_state(3, 7, EntityStatus::Alive);
+        generator.force_state(0, 2, EntityStatus::Dead);
+        generator.force_state(1, u32::MAX, EntityStatus::Tombstone);
+
+        assert_eq!(generator.alive_count(), 1);
+        assert_eq!(generator.tombstone_count(), 1);
+        assert_eq!(generator.despawned, vec![2, 0]);
+        assert!(generator.is_alive(Entity::from_bits(7 << 32 | 3)));
+        assert_eq!(generator.spawn().to_bits(), 3 << 32);
+    }
+
+    #[test]
+    fn are_alive() {
+        let mut generator = EntityGenerator::new();
+        let mut entities = generator.spawn_batch(6);
+        generator.despawn_batch(&[entities[1], entities[4]]);
+        entities.push(generator.spawn());
+        entities.push(EntityGenerator::new().spawn());
+        entities.push(Entity::from_bits(1 << 32 | 100));
+
+        let mut out = vec![true; 50];
+        generator.are_alive(&entities, &mut out);
+        let expected: Vec<_> = entities.iter().map(|&e| generator.is_alive(e)).collect();
+        assert_eq!(out, expected);
+        assert_eq!(out.iter().filter(|&&alive| alive).count(), 5);
+
+        generator.are_alive(&entities[..2], &mut out);
+        assert_eq!(out, vec![true, false]);
+    }
+
+    #[test]
+    fn relocate() {
+        let mut generator = EntityGenerator::new();
+        generator.set_record_despawns(true);
+        let entities = generator.spawn_batch(10);
+        generator.despawn(entities[2]);
+
+        let moved = generator.relocate(entities[8], 2).unwrap();
+        assert_eq!((moved.index(), moved.generation()), (2, 2));
+        assert!(generator.is_alive(moved));
+        assert!(generator.is_alive(entities[8]) == false);
+        assert!(generator.is_alive(entities[2]) == false);
+        assert_eq!(generator.alive_count(), 9);
+        assert_eq!(
+            generator.drain_despawned().collect::<Vec<_>>(),
+            vec![entities[2]]
+        );
+
+        // The old slot is free for the next spawn
+        assert_eq!(generator.spawn().index(), 8);
+
+        let far = generator.relocate(entities[0], 12).unwrap();
+        assert_eq!((far.index(), far.generation()), (12, 1));
+        assert_eq!(generator.capacity(), 13);
+        assert_eq!(generator.alive_count(), 10);
+
+        assert_eq!(
+            generator.relocate(entities[1], 3),
+            Err(RelocateError::Occupied)
+        );
+        assert_eq!(
+            generator.relocate(entities[8], 0),
+            Err(RelocateError::NotAlive)
+        );
+        generator.force_state(0, u32::MAX, EntityStatus::Tombstone);
+        assert_eq!(
+            generator.relocate(entities[1], 0),
+            Err(RelocateError::Tombstone)
+        );
+        assert!(generator.is_alive(entities[1]));
+    }
+
+    #[test]
+    fn count_alive_in_range() {
+        let mut generator = EntityGenerator::new();
+        generator.spawn_batch(20);
+        // Every third index is dead
+        generator.retain(|e| e.index() % 3 != 0);
+
+        assert_eq!(generator.count_alive_in_range(0..20), 13);
+        assert_eq!(generator.count_alive_in_range(0..3), 2);
+        assert_eq!(generator.count_alive_in_range(3..4), 0);
+        assert_eq!(generator.count_alive_in_range(10..15), 4);
+        assert_eq!(generator.count_alive_in_range(5..5), 0);
+        assert_eq!(generator.count_alive_in_range(15..1000), 3);
+        assert_eq!(generator.count_alive_in_range(500..1000), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let backwards = 12..4;
+        assert_eq!(generator.count_alive_in_range(backwards), 0);
+
+        let _reserved = generator.reserve_entities(5);
+        assert_eq!(generator.count_alive_in_range(18..23), 4);
+        assert_eq!(generator.count_alive_in_range(30..40), 0);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(4);
+        generator.despawn(entities[2]);
+
+        let mut survivors = Vec::new();
+        for e in &generator {
+            survivors.push(e);
+        }
+        assert_eq!(survivors, vec![entities[0], entities[1], entities[3]]);
+    }
+
+    #[test]
+    fn random_generation_step_burn_relocate() {
+        // `burn_generation` and `relocate` take the same random step that a spawn would
+        let strategy = GenStrategy::RandomStep { seed: 42 };
+        let mut spawned = EntityGenerator::with_generation_strategy(strategy);
+        let mut burnt = EntityGenerator::with_generation_strategy(strategy);
+        let mut relocated = EntityGenerator::with_generation_strategy(strategy);
+        for generator in [&mut spawned, &mut burnt, &mut relocated] {
+            let e = generator.spawn_batch(2)[0];
+            generator.despawn(e);
+        }
+
+        let respawned = spawned.spawn();
+        burnt.burn_generation(0);
+        let moved = relocated.entity_at_index(1).unwrap();
+        let moved = relocated.relocate(moved, 0).unwrap();
+        assert!(respawned.generation() > 2);
+        assert_eq!(burnt.slots().next().unwrap().1, respawned.generation());
+        assert_eq!(moved.generation(), respawned.generation());
+    }
+
+    #[test]
+    fn random_generation_step() {
+        let mut generator =
+            EntityGenerator::with_generation_strategy(GenStrategy::RandomStep { seed: 42 });
+        let mut e = generator.spawn();
+        let mut generations = vec![e.generation()];
+        for _ in 0..5 {
+            generator.despawn(e);
+            e = generator.spawn();
+            assert_eq!(e.index(), 0);
+            generations.push(e.generation());
+        }
+        // Always moves forward but not one at a time
+        assert!(generations.windows(2).all(|w| w[0] < w[1]));
+        assert!(generations.windows(2).any(|w| w[1] - w[0] > 1));
+
+        // Same seed, same generations
+        let mut replay =
+            EntityGenerator::with_generation_strategy(GenStrategy::RandomStep { seed: 42 });
+        let mut e = replay.spawn();
+        for &generation in generations[1..].iter() {
+            replay.despawn(e);
+            e = replay.spawn();
+            assert_eq!(e.generation(), generation);
+        }
+
+        // One short of the end so any step other than 1 overshoots (which this seed does next), the slot gets retired instead of reused
+        generator.despawn(e);
+        generator.force_state(0, u32::MAX - 1, EntityStatus::Dead);
+        let rng = generator.rng;
+        let fresh = generator.spawn();
+        assert_eq!(fresh.index(), 1);
+        assert_eq!(generator.tombstone_count(), 1);
+        assert_eq!(
+            generator.slots().next(),
+            Some((0, u32::MAX - 1, SlotStatus::Tombstone))
+        );
+
+        // Burning a generation that overshoots retires the slot the same way
+        let mut burnt =
+            EntityGenerator::with_generation_strategy(GenStrategy::RandomStep { seed: 42 });
+        let e = burnt.spawn();
+        burnt.despawn(e);
+        burnt.force_state(0, u32::MAX - 1, EntityStatus::Dead);
+        burnt.rng = rng;
+        burnt.burn_generation(0);
+        assert_eq!(burnt.tombstone_count(), 1);
+        assert_eq!(burnt.slots().next(), generator.slots().next());
+        let last = Entity::from_raw_parts(0, u32::MAX - 1);
+        assert_eq!(burnt.liveness(last), generator.liveness(last));
+        assert_eq!(burnt.liveness(last), Liveness::Despawned);
     }
 
     // GUIDE: if only we could test having more than u32::MAX entities would panic alas we would run out of ram
@@ -0,0 +1,145 @@
+use crate::Entity;
+use alloc::vec::Vec;
+
+// Like a `HashMap<Entity, V>` except the entity's index is used directly as the position in a vec so there's
+// no hashing. The whole entity is stored next to the value so a lookup with an old entity whose slot has been
+// reused misses instead of finding the new occupant's value. Like `==` on entities this doesn't look at which
+// world an entity came from, so keep one map per world
+pub struct EntityMap<V> {
+    slots: Vec<Option<(Entity, V)>>,
+    len: usize,
+}
+
+impl<V> EntityMap<V> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // Returns the old value if `entity` was already in the map, a value left behind by an
+    // old entity in the same slot is dropped rather than returned since it isn't `entity`'s
+    pub fn insert(&mut self, entity: Entity, value: V) -> Option<V> {
+        let index = entity.index() as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        let old = match self.slots[index].take() {
+            Some((old_entity, old_value)) if old_entity == entity => Some(old_value),
+            Some(_) => {
+                self.len -= 1;
+                None
+            }
+            None => None,
+        };
+        if old.is_none() {
+            self.len += 1;
+        }
+        self.slots[index] = Some((entity, value));
+        old
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&V> {
+        match self.slots.get(entity.index() as usize)? {
+            Some((stored, value)) if *stored == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut V> {
+        match self.slots.get_mut(entity.index() as usize)? {
+            Some((stored, value)) if *stored == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<V> {
+        let slot = self.slots.get_mut(entity.index() as usize)?;
+        match slot {
+            Some((stored, _)) if *stored == entity => (),
+            _ => return None,
+        }
+        self.len -= 1;
+        slot.take().map(|(_, value)| value)
+    }
+
+    // In index order
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &V)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(entity, value)| (*entity, value)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<V> Default for EntityMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EntityMap;
+    use crate::entity::EntityGenerator;
+    use crate::Entity;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(3);
+        let mut map = EntityMap::new();
+
+        assert_eq!(map.insert(entities[2], "c"), None);
+        assert_eq!(map.insert(entities[0], "a"), None);
+        assert_eq!(map.insert(entities[0], "A"), Some("a"));
+        assert_eq!(map.get(entities[0]), Some(&"A"));
+        assert_eq!(map.get(entities[1]), None);
+        assert_eq!(map.len(), 2);
+
+        *map.get_mut(entities[2]).unwrap() = "C";
+        let all: Vec<_> = map.iter().collect();
+        assert_eq!(all, vec![(entities[0], &"A"), (entities[2], &"C")]);
+
+        assert_eq!(map.remove(entities[0]), Some("A"));
+        assert_eq!(map.remove(entities[0]), None);
+        assert!(map.contains(entities[0]) == false);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn stale_generation() {
+        let mut generator = EntityGenerator::new();
+        let old = generator.spawn();
+        let mut map = EntityMap::new();
+        map.insert(old, 1);
+
+        generator.despawn(old);
+        let new = generator.spawn();
+        assert_eq!(new.index(), old.index());
+        assert_eq!(map.get(new), None);
+        assert_eq!(map.remove(new), None);
+
+        // The new entity takes over the slot and the old one stops seeing anything
+        assert_eq!(map.insert(new, 2), None);
+        assert_eq!(map.get(old), None);
+        assert_eq!(map.get(new), Some(&2));
+        assert_eq!(map.len(), 1);
+
+        // A handle loaded from a save finds the value stored with the original
+        assert_eq!(map.get(Entity::from_bits(new.to_bits())), Some(&2));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Entity;
+use std::collections::HashMap;
+
+// Returned by `set_parent` when the new parent is the child itself or one of its descendants,
+// letting that through would make a loop that cascading despawns would never get out of
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CycleError;
+
+// Parent/child links between entities, the world keeps this up to date when entities get despawned
+// GUIDE: this could be a `Parent`/`Children` component pair but then anyone could insert a `Parent` without
+// the matching `Children` and the two would get out of sync
+#[derive(Default)]
+pub(crate) struct Hierarchy {
+    parents: HashMap<Entity, Entity>,
+    children: HashMap<Entity, Vec<Entity>>,
+}
+
+impl Hierarchy {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.parents.get(&entity).copied()
+    }
+
+    pub(crate) fn children(&self, entity: Entity) -> &[Entity] {
+        match self.children.get(&entity) {
+            Some(children) => children,
+            None => &[],
+        }
+    }
+
+    // Walks up from `entity` so this is O(depth)
+    pub(crate) fn is_ancestor(&self, ancestor: Entity, mut entity: Entity) -> bool {
+        while let Some(parent) = self.parent(entity) {
+            if parent == ancestor {
+                return true;
+            }
+            entity = parent;
+        }
+        false
+    }
+
+    pub(crate) fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), CycleError> {
+        if child == parent || self.is_ancestor(child, parent) {
+            return Err(CycleError);
+        }
+
+        self.detach(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+        Ok(())
+    }
+
+    // Removes the link to `entity`'s parent if it has one, its own children are left alone
+    pub(crate) fn detach(&mut self, entity: Entity) {
+        let parent = match self.parents.remove(&entity) {
+            Some(parent) => parent,
+            None => return,
+        };
+        let siblings = self.children.get_mut(&parent).unwrap();
+        siblings.retain(|&sibling| sibling != entity);
+        if siblings.is_empty() {
+            self.children.remove(&parent);
+        }
+    }
+
+    // Forgets about `entity` completely and hands back its children, which are left without a parent
+    pub(crate) fn remove(&mut self, entity: Entity) -> Vec<Entity> {
+        self.detach(entity);
+        let children = self.children.remove(&entity).unwrap_or_default();
+        for child in children.iter() {
+            self.parents.remove(child);
+        }
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CycleError, Hierarchy};
+    use crate::entity::EntityGenerator;
+
+    #[test]
+    fn set_parent() {
+        let mut generator = EntityGenerator::new();
+        let entities = generator.spawn_batch(4);
+        let mut hierarchy = Hierarchy::new();
+        hierarchy.set_parent(entities[1], entities[0]).unwrap();
+        hierarchy.set_parent(entities[2], entities[0]).unwrap();
+        hierarchy.set_parent(entities[3], entities[1]).unwrap();
+
+        assert_eq!(hierarchy.children(entities[0]), &[entities[1], entities[2]]);
+        assert_eq!(hierarchy.parent(entities[3]), Some(entities[1]));
+        assert!(hierarchy.is_ancestor(entities[0], entities[3]));
+        assert!(hierarchy.is_ancestor(entities[2], entities[3]) == false);
+
+        // Reparenting moves the child out of its old parent's children
+        hierarchy.set_parent(entities[3], entities[2]).unwrap();
+        assert!(hierarchy.children(entities[1]).is_empty());
+        assert_eq!(hierarchy.children(entities[2]), &[entities[3]]);
+
+        let children = hierarchy.remove(entities[0]);
+        assert_eq!(children, vec![entities[1], entities[2]]);
+        assert_eq!(hierarchy.parent(entities[2]), None);
+        assert_eq!(hierarchy.parent(entities[3]), Some(entities[2]));
+    }
+
+    #[test]
+    fn set_parent_cycle() {
+        let mut generator = EntityGenerator::new();
+        let (a, b, c) = (generator.spawn(), generator.spawn(), generator.spawn());
+        let mut hierarchy = Hierarchy::new();
+        hierarchy.set_parent(b, a).unwrap();
+        hierarchy.set_parent(c, b).unwrap();
+
+        assert_eq!(hierarchy.set_parent(a, c), Err(CycleError));
+        assert_eq!(hierarchy.set_parent(a, a), Err(CycleError));
+        assert_eq!(hierarchy.parent(a), None);
+        assert_eq!(hierarchy.children(c), &[]);
+        assert_eq!(hierarchy.children(a), &[b]);
+        assert_eq!(hierarchy.parent(c), Some(b));
+    }
+}
@@ -1,4 +1,34 @@
 #![forbid(unsafe_code)]
+// Only the entity allocator and the bits built directly on it are available without `std`, see the README
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+// GUIDE: `World` is the public face of the crate and only drives part of the generator, the rest is only exercised by tests for now
+#![allow(dead_code)]
+// I like my `== false` okay
+#![allow(clippy::bool_comparison)]
 
-mod entity;
-pub use entity::Entity;
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod bundle;
+#[cfg(feature = "std")]
+mod command;
+mod compact;
+pub mod entity;
+mod entity_map;
+#[cfg(feature = "std")]
+mod hierarchy;
+#[cfg(feature = "std")]
+mod storage;
+#[cfg(feature = "std")]
+mod world;
+#[cfg(feature = "std")]
+pub use bundle::Bundle;
+#[cfg(feature = "std")]
+pub use command::CommandBuffer;
+pub use compact::CompactEntity;
+pub use entity::{Entity, StrictMode};
+pub use entity_map::EntityMap;
+#[cfg(feature = "std")]
+pub use hierarchy::CycleError;
+#[cfg(feature = "std")]
+pub use world::{EntityBuilder, WeakEntity, World};
@@ -0,0 +1,656 @@
+use crate::Entity;
+use std::any::Any;
+use std::collections::BTreeMap;
+
+// A sparse set, components are packed together in `dense` so iterating them is fast and `sparse`
+// maps an entity's index to where its component lives in `dense`
+pub(crate) struct ComponentStorage<T> {
+    dense: Vec<T>,
+    sparse: Vec<Option<u32>>,
+    // The entity each component in `dense` belongs to, this is how we check the generation so that
+    // a stale entity can't read the component of whatever entity is reusing its index
+    entities: Vec<Entity>,
+    // The version each component in `dense` was last changed at, a component counts as changed
+    // whenever a `&mut` to it is handed out since we can't tell if it actually got written to
+    versions: Vec<u64>,
+    // Bumped every time something gets changed
+    // GUIDE: this used to be a u32, at a million changes a frame that wraps in about 70 seconds and then every
+    // `changed_since` is wrong. A u64 takes longer than anyone's game will be running
+    version: u64,
+}
+
+impl<T> ComponentStorage<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            versions: Vec::new(),
+            version: 0,
+        }
+    }
+
+    // The version of the most recent change, hang on to this and pass it to `changed_since`
+    // later to find out what's been changed after this point
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn next_version(&mut self) -> u64 {
+        self.version += 1;
+        self.version
+    }
+
+    pub(crate) fn changed_since(&self, entity: Entity, version: u64) -> bool {
+        match self.dense_index(entity) {
+            Some(dense_idx) => self.versions[dense_idx] > version,
+            None => false,
+        }
+    }
+
+    pub(crate) fn iter_changed(&self, version: u64) -> impl Iterator<Item = (Entity, &T)> {
+        self.iter()
+            .zip(self.versions.iter())
+            .filter(move |&(_, &changed)| changed > version)
+            .map(|(item, _)| item)
+    }
+
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let dense_idx = (*self.sparse.get(entity.index() as usize)?)? as usize;
+        match self.entities[dense_idx] == entity {
+            true => Some(dense_idx),
+            false => None,
+        }
+    }
+
+    // Returns the component that was already on the entity if there was one
+    pub(crate) fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        let index = entity.index() as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+
+        let version = self.next_version();
+        if let Some(dense_idx) = self.sparse[index] {
+            let dense_idx = dense_idx as usize;
+            self.versions[dense_idx] = version;
+            let previous = std::mem::replace(&mut self.dense[dense_idx], component);
+            // GUIDE: the World removes components on despawn so this should only be hit by people using
+            // storages by hand, a component left behind by a stale entity isn't this entity's to hand back
+            let previous_owner = std::mem::replace(&mut self.entities[dense_idx], entity);
+            return match previous_owner == entity {
+                true => Some(previous),
+                false => None,
+            };
+        }
+
+        self.sparse[index] = Some(self.dense.len() as u32);
+        self.dense.push(component);
+        self.entities.push(entity);
+        self.versions.push(version);
+        None
+    }
+
+    // Like `insert` except an existing component is left alone and `component` is handed back instead
+    pub(crate) fn try_insert(&mut self, entity: Entity, component: T) -> Result<(), T> {
+        if self.contains(entity) {
+            return Err(component);
+        }
+        self.insert(entity, component);
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    pub(crate) fn get(&self, entity: Entity) -> Option<&T> {
+        let dense_idx = self.dense_index(entity)?;
+        Some(&self.dense[dense_idx])
+    }
+
+    pub(crate) fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let dense_idx = self.dense_index(entity)?;
+        self.versions[dense_idx] = self.next_version();
+        Some(&mut self.dense[dense_idx])
+    }
+
+    // Both components at once for things like swapping values between two entities, `None` if either
+    // entity doesn't have one or they're the same entity since that would be two `&mut` to one component
+    pub(crate) fn get_disjoint_mut(&mut self, a: Entity, b: Entity) -> Option<(&mut T, &mut T)> {
+        let a_idx = self.dense_index(a)?;
+        let b_idx = self.dense_index(b)?;
+        if a_idx == b_idx {
+            return None;
+        }
+
+        let version = self.next_version();
+        self.versions[a_idx] = version;
+        self.versions[b_idx] = version;
+        // Whichever one comes second in `dense` ends up at the start of the second half
+        let (low, high) = self.dense.split_at_mut(a_idx.max(b_idx));
+        match a_idx < b_idx {
+            true => Some((&mut low[a_idx], &mut high[0])),
+            false => Some((&mut high[0], &mut low[b_idx])),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<T> {
+        let dense_idx = self.dense_index(entity)?;
+        self.sparse[entity.index() as usize] = None;
+
+        // Swap the last component into the hole so we don't have to shift everything after it down,
+        // then point the moved entity's sparse entry at its new position
+        self.entities.swap_remove(dense_idx);
+        self.versions.swap_remove(dense_idx);
+        let component = self.dense.swap_remove(dense_idx);
+        if let Some(moved) = self.entities.get(dense_idx) {
+            self.sparse[moved.index() as usize] = Some(dense_idx as u32);
+        }
+        Some(component)
+    }
+
+    // Iterates in the order components are laid out in `dense`, that's insertion order up until something
+    // gets removed and the last component is swapped into its place
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.dense.iter())
+    }
+
+    // Iterates in entity index order instead, this walks `sparse` so it's slower than `iter` when there
+    // are a lot of entities without this component but the order only depends on which entities have one
+    pub(crate) fn iter_sorted(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.sparse.iter().filter_map(move |&dense_idx| {
+            let dense_idx = dense_idx? as usize;
+            Some((self.entities[dense_idx], &self.dense[dense_idx]))
+        })
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let version = self.next_version();
+        self.entities
+            .iter()
+            .copied()
+            .zip(self.dense.iter_mut().zip(self.versions.iter_mut()))
+            .map(move |(entity, (component, changed))| {
+                *changed = version;
+                (entity, component)
+            })
+    }
+
+    // Same as `iter_mut` but split across rayon's threads, every element of `dense` and `versions` is
+    // its own `&mut` so nothing is shared between the pieces
+    #[cfg(feature = "rayon")]
+    pub(crate) fn par_iter_mut(
+        &mut self,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = (Entity, &mut T)>
+    where
+        T: Send,
+    {
+        use rayon::prelude::*;
+
+        let version = self.next_version();
+        self.entities
+            .par_iter()
+            .copied()
+            .zip(self.dense.par_iter_mut().zip(self.versions.par_iter_mut()))
+            .map(move |(entity, (component, changed))| {
+                *changed = version;
+                (entity, component)
+            })
+    }
+
+    // Pairs up every component in this storage with the other storage's component for the same entity,
+    // whichever storage is smaller gets walked and the other one is looked up through its sparse array
+    pub(crate) fn join_mut<'a, U>(
+        &'a mut self,
+        other: &'a mut ComponentStorage<U>,
+    ) -> Box<dyn Iterator<Item = (Entity, &'a mut T, &'a mut U)> + 'a> {
+        match self.len() <= other.len() {
+            true => Box::new(self.join_lookup(other)),
+            false => Box::new(
+                other
+                    .join_lookup(self)
+                    .map(|(entity, component, other_component)| {
+                        (entity, other_component, component)
+                    }),
+            ),
+        }
+    }
+
+    // We can't index into `other.dense` for each entity and hand out a `&mut` since the borrow checker
+    // can't see that every index is different, so `other`'s components are split off one at a time
+    // with `SplitSlice` instead
+    fn join_lookup<'a, U>(
+        &'a mut self,
+        other: &'a mut ComponentStorage<U>,
+    ) -> impl Iterator<Item = (Entity, &'a mut T, &'a mut U)> {
+        let version = self.next_version();
+        let other_version = other.next_version();
+        let other_sparse = &other.sparse;
+        let other_entities = &other.entities;
+        let mut other_dense = SplitSlice::new(&mut other.dense);
+        let mut other_versions = SplitSlice::new(&mut other.versions);
+
+        self.entities
+            .iter()
+            .copied()
+            .zip(self.dense.iter_mut().zip(self.versions.iter_mut()))
+            .filter_map(move |(entity, (component, changed))| {
+                let dense_idx = (*other_sparse.get(entity.index() as usize)?)? as usize;
+                if other_entities[dense_idx] != entity {
+                    return None;
+                }
+                let other_component = other_dense.take(dense_idx)?;
+                *other_versions.take(dense_idx)? = other_version;
+                *changed = version;
+                Some((entity, component, other_component))
+            })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    // Drops every component but hangs on to the allocations. The version isn't reset so anything
+    // holding on to an old version still gets sensible answers from `changed_since`
+    pub(crate) fn clear(&mut self) {
+        self.dense.clear();
+        self.sparse.clear();
+        self.entities.clear();
+        self.versions.clear();
+    }
+}
+
+impl<T> Default for ComponentStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Type erased storage for a single component type so that `World` can hold storages for every
+// component type in one map and still remove an entity's components without knowing their types
+pub(crate) trait AnyStorage: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn remove_entity(&mut self, entity: Entity);
+}
+
+impl<T: 'static> AnyStorage for ComponentStorage<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+}
+
+// Hands out a `&mut` to each element of a slice in whatever order they're asked for, at most once each. The
+// parts that haven't been handed out yet are kept as separate sub slices keyed by where they start, so this only
+// allocates as the slice gets split up rather than up front for every element
+struct SplitSlice<'a, X> {
+    pieces: BTreeMap<usize, &'a mut [X]>,
+}
+
+impl<'a, X> SplitSlice<'a, X> {
+    fn new(slice: &'a mut [X]) -> Self {
+        let mut pieces = BTreeMap::new();
+        pieces.insert(0, slice);
+        Self { pieces }
+    }
+
+    fn take(&mut self, idx: usize) -> Option<&'a mut X> {
+        let start = *self.pieces.range(..=idx).next_back()?.0;
+        let piece = self.pieces.remove(&start)?;
+        if idx - start >= piece.len() {
+            // Already handed out
+            self.pieces.insert(start, piece);
+            return None;
+        }
+
+        let (left, rest) = piece.split_at_mut(idx - start);
+        let (item, right) = rest.split_first_mut()?;
+        if left.is_empty() == false {
+            self.pieces.insert(start, left);
+        }
+        if right.is_empty() == false {
+            self.pieces.insert(idx + 1, right);
+        }
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityGenerator;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let e1 = generator.spawn();
+        let e2 = generator.spawn();
+
+        assert_eq!(storage.insert(e1, 10_u32), None);
+        assert_eq!(storage.insert(e2, 20), None);
+        assert_eq!(storage.insert(e1, 11), Some(10));
+        assert_eq!(storage.get(e1), Some(&11));
+
+        *storage.get_mut(e2).unwrap() += 1;
+        assert_eq!(storage.get(e2), Some(&21));
+
+        assert_eq!(storage.remove(e1), Some(11));
+        assert_eq!(storage.remove(e1), None);
+        assert_eq!(storage.get(e1), None);
+        assert_eq!(storage.get(e2), Some(&21));
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let e1 = generator.spawn();
+
+        assert_eq!(storage.try_insert(e1, 10_u32), Ok(()));
+        let version = storage.version();
+        assert_eq!(storage.try_insert(e1, 20), Err(20));
+        assert_eq!(storage.get(e1), Some(&10));
+        assert_eq!(storage.version(), version);
+
+        // A component left behind by an old entity in the same slot doesn't count as already being there
+        generator.despawn(e1);
+        let e2 = generator.spawn();
+        assert_eq!(storage.try_insert(e2, 30), Ok(()));
+        assert_eq!(storage.get(e2), Some(&30));
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn remove_middle() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(5);
+        for (i, &e) in entities.iter().enumerate() {
+            storage.insert(e, i * 10);
+        }
+
+        // The last component gets swapped into the hole so its sparse index has to be fixed up
+        assert_eq!(storage.remove(entities[1]), Some(10));
+        assert_eq!(
+            storage.entities,
+            vec![entities[0], entities[4], entities[2], entities[3]]
+        );
+        assert_eq!(storage.get(entities[4]), Some(&40));
+        *storage.get_mut(entities[4]).unwrap() += 1;
+        assert_eq!(storage.get(entities[4]), Some(&41));
+        for &i in [0, 2, 3].iter() {
+            assert_eq!(storage.get(entities[i]), Some(&(i * 10)));
+        }
+
+        // Removing the last one has nothing to swap with
+        assert_eq!(storage.remove(entities[3]), Some(30));
+        assert_eq!(storage.get(entities[4]), Some(&41));
+        assert_eq!(storage.len(), 3);
+    }
+
+    #[test]
+    fn stale_generation() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let e1 = generator.spawn();
+        storage.insert(e1, 10_u32);
+
+        // e2 reuses e1's index but shouldnt be able to see e1's component
+        generator.despawn(e1);
+        let e2 = generator.spawn();
+        assert_eq!(e1.index(), e2.index());
+        assert_eq!(storage.get(e2), None);
+        assert_eq!(storage.remove(e2), None);
+
+        assert_eq!(storage.insert(e2, 20), None);
+        assert_eq!(storage.get(e1), None);
+        assert!(storage.get_mut(e1).is_none());
+        assert_eq!(storage.get(e2), Some(&20));
+    }
+
+    #[test]
+    fn old_handle_after_respawn() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let old = generator.spawn();
+        storage.insert(old, 1_u32);
+
+        // Whoever gets the slot next owns whatever is stored there, the old handle mustn't reach it
+        generator.despawn(old);
+        let new = generator.spawn();
+        assert_eq!(new.index(), old.index());
+        assert_eq!(new.generation(), old.generation() + 1);
+        storage.insert(new, 2);
+
+        assert_eq!(storage.get(old), None);
+        assert!(storage.get_mut(old).is_none());
+        assert!(storage.contains(old) == false);
+        assert_eq!(storage.remove(old), None);
+        assert_eq!(storage.get(new), Some(&2));
+    }
+
+    #[test]
+    fn change_detection() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(4);
+        for (i, &e) in entities.iter().enumerate() {
+            storage.insert(e, i);
+        }
+
+        let since = storage.version();
+        assert!(entities
+            .iter()
+            .all(|&e| storage.changed_since(e, since) == false));
+        assert_eq!(storage.iter_changed(since).count(), 0);
+
+        *storage.get_mut(entities[1]).unwrap() += 10;
+        *storage.get_mut(entities[3]).unwrap() += 10;
+        assert!(storage.changed_since(entities[1], since));
+        assert!(storage.changed_since(entities[2], since) == false);
+
+        // Removing shuffles things around in dense, versions need to move with their component
+        storage.remove(entities[0]);
+        let changed: Vec<_> = storage.iter_changed(since).collect();
+        assert_eq!(changed, vec![(entities[3], &13), (entities[1], &11)]);
+
+        let since = storage.version();
+        storage.insert(entities[0], 0);
+        let changed: Vec<_> = storage.iter_changed(since).collect();
+        assert_eq!(changed, vec![(entities[0], &0)]);
+    }
+
+    #[test]
+    fn version_past_u32() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let e1 = generator.spawn();
+        storage.insert(e1, 1_u32);
+        storage.version = u32::MAX as u64;
+
+        let version = storage.version();
+        *storage.get_mut(e1).unwrap() += 1;
+        assert!(storage.changed_since(e1, version));
+        assert!(storage.changed_since(e1, storage.version()) == false);
+    }
+
+    #[test]
+    fn join_mut_only_changes_joined() {
+        let mut generator = EntityGenerator::new();
+        let mut numbers = ComponentStorage::new();
+        let mut flags = ComponentStorage::new();
+        let entities = generator.spawn_batch(3);
+        numbers.insert(entities[0], 0_u32);
+        numbers.insert(entities[1], 1_u32);
+        flags.insert(entities[1], false);
+        flags.insert(entities[2], false);
+
+        let (numbers_since, flags_since) = (numbers.version(), flags.version());
+        for (_, number, flag) in numbers.join_mut(&mut flags) {
+            *flag = *number == 1;
+        }
+
+        let changed: Vec<_> = numbers
+            .iter_changed(numbers_since)
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(changed, vec![entities[1]]);
+        let changed: Vec<_> = flags.iter_changed(flags_since).collect();
+        assert_eq!(changed, vec![(entities[1], &true)]);
+    }
+
+    #[test]
+    fn join_mut_smaller_other() {
+        let mut generator = EntityGenerator::new();
+        let mut numbers = ComponentStorage::new();
+        let mut flags = ComponentStorage::new();
+        let entities = generator.spawn_batch(10);
+        for (i, &e) in entities.iter().enumerate() {
+            numbers.insert(e, i);
+        }
+        flags.insert(entities[7], false);
+        flags.insert(entities[2], false);
+        flags.insert(entities[7], true);
+
+        // `flags` is the one that gets walked but the components still come out in the right order
+        let joined: Vec<_> = numbers
+            .join_mut(&mut flags)
+            .map(|(e, number, flag)| (e, *number, *flag))
+            .collect();
+        assert_eq!(
+            joined,
+            vec![(entities[7], 7, true), (entities[2], 2, false)]
+        );
+    }
+
+    #[test]
+    fn split_slice() {
+        let mut items = [0, 1, 2, 3, 4, 5];
+        let mut split = SplitSlice::new(&mut items);
+        let taken: Vec<_> = [3, 0, 5, 3, 1, 6, 4, 2]
+            .iter()
+            .map(|&idx| split.take(idx).map(|item| *item))
+            .collect();
+        assert_eq!(
+            taken,
+            vec![
+                Some(3),
+                Some(0),
+                Some(5),
+                None,
+                Some(1),
+                None,
+                Some(4),
+                Some(2)
+            ]
+        );
+        assert!(split.pieces.is_empty());
+    }
+
+    #[test]
+    fn iter() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(3);
+        storage.insert(entities[2], 'c');
+        storage.insert(entities[0], 'a');
+        storage.insert(entities[1], 'b');
+
+        let items: Vec<_> = storage.iter().collect();
+        assert_eq!(
+            items,
+            vec![
+                (entities[2], &'c'),
+                (entities[0], &'a'),
+                (entities[1], &'b')
+            ]
+        );
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(4);
+        for (i, &e) in entities.iter().take(3).enumerate() {
+            storage.insert(e, i * 10);
+        }
+
+        let version = storage.version();
+        let (a, b) = storage.get_disjoint_mut(entities[2], entities[0]).unwrap();
+        std::mem::swap(a, b);
+        assert_eq!(storage.get(entities[0]), Some(&20));
+        assert_eq!(storage.get(entities[2]), Some(&0));
+        assert!(storage.changed_since(entities[0], version));
+        assert!(storage.changed_since(entities[2], version));
+        assert!(storage.changed_since(entities[1], version) == false);
+
+        let (a, b) = storage.get_disjoint_mut(entities[0], entities[1]).unwrap();
+        *a += 1;
+        *b += 2;
+        assert_eq!(storage.get(entities[0]), Some(&21));
+        assert_eq!(storage.get(entities[1]), Some(&12));
+
+        assert!(storage.get_disjoint_mut(entities[1], entities[1]).is_none());
+        assert!(storage.get_disjoint_mut(entities[1], entities[3]).is_none());
+    }
+
+    #[test]
+    fn clear() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(10);
+        for (i, &e) in entities.iter().enumerate() {
+            storage.insert(e, i);
+        }
+        let capacity = storage.dense.capacity();
+
+        storage.clear();
+        assert_eq!(storage.len(), 0);
+        assert!(entities.iter().all(|&e| storage.contains(e) == false));
+        assert_eq!(storage.dense.capacity(), capacity);
+
+        let version = storage.version();
+        storage.insert(entities[3], 30);
+        assert_eq!(storage.get(entities[3]), Some(&30));
+        assert!(storage.changed_since(entities[3], version));
+    }
+
+    #[test]
+    fn iter_sorted() {
+        let mut generator = EntityGenerator::new();
+        let mut storage = ComponentStorage::new();
+        let entities = generator.spawn_batch(6);
+        for &i in [4, 1, 5, 0, 3].iter() {
+            storage.insert(entities[i], i);
+        }
+        storage.remove(entities[1]);
+
+        let dense: Vec<_> = storage.iter().map(|(_, &i)| i).collect();
+        assert_eq!(dense, vec![4, 3, 5, 0]);
+
+        let sorted: Vec<_> = storage.iter_sorted().collect();
+        assert!(sorted
+            .windows(2)
+            .all(|pair| pair[0].0.index() < pair[1].0.index()));
+        assert_eq!(
+            sorted,
+            vec![
+                (entities[0], &0),
+                (entities[3], &3),
+                (entities[4], &4),
+                (entities[5], &5)
+            ]
+        );
+    }
+}
@@ -0,0 +1,757 @@
+use crate::bundle::Bundle;
+use crate::command::{Command, CommandBuffer};
+use crate::entity::{EntityGenerator, StrictMode};
+use crate::hierarchy::{CycleError, Hierarchy};
+use crate::storage::{AnyStorage, ComponentStorage};
+use crate::Entity;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+pub struct World {
+    entities: EntityGenerator,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    hierarchy: Hierarchy,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self {
+            entities: EntityGenerator::new(),
+            storages: HashMap::new(),
+            hierarchy: Hierarchy::new(),
+        }
+    }
+
+    // Silent by default, see `StrictMode` for what the other modes do
+    pub fn set_strict_mode(&mut self, mode: StrictMode) {
+        self.entities.set_strict_mode(mode);
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        self.entities.spawn()
+    }
+
+    // Alive straight away even though it only takes `&self`, see `CommandBuffer::spawn`
+    pub(crate) fn reserve_entity(&self) -> Entity {
+        self.entities.reserve_entity()
+    }
+
+    // Commands are applied in the order they were recorded, despawns go through `despawn` so they take
+    // components and children with them and anything already dead is skipped
+    pub fn apply(&mut self, buffer: CommandBuffer) {
+        self.entities.flush();
+        for &command in buffer.commands() {
+            match command {
+                Command::Spawn(_) => (),
+                Command::Despawn(entity) => {
+                    self.despawn(entity);
+                }
+            }
+        }
+    }
+
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let entity = self.spawn();
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    // The entity is spawned straight away, `with` just inserts components on it as you go
+    pub fn spawn_entity(&mut self) -> EntityBuilder<'_> {
+        let entity = self.spawn();
+        EntityBuilder {
+            world: self,
+            entity,
+        }
+    }
+
+    // Despawning an entity also removes all of its components, we do that before telling the generator
+    // so that the index can't be handed out again while there are still components stored for it.
+    // Any children of the entity get despawned along with it, and their children, and so on
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if self.is_alive(entity) == false {
+            // Nothing to remove, the generator still gets asked so that `StrictMode` hears about it
+            return self.entities.despawn(entity);
+        }
+
+        let mut to_despawn = vec![entity];
+        while let Some(entity) = to_despawn.pop() {
+            // Despawning always removes an entity from the hierarchy so this shouldn't happen, but if a child
+            // somehow died without us hearing about it there's nothing left to do for it
+            if self.is_alive(entity) == false {
+                continue;
+            }
+
+            to_despawn.extend(self.hierarchy.remove(entity));
+            for storage in self.storages.values_mut() {
+                storage.remove_entity(entity);
+            }
+            self.entities.despawn(entity);
+        }
+        true
+    }
+
+    // Errors if `parent` is `child` or one of its descendants, the hierarchy is left as it was.
+    // Panics if either entity is dead
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), CycleError> {
+        if self.is_alive(child) == false || self.is_alive(parent) == false {
+            panic!("Attempted to set the parent of a dead entity or to a dead entity");
+        }
+        // Same as `insert`, only this world's own handles go in
+        let child = self.entities.entity_at_index(child.index()).unwrap();
+        let parent = self.entities.entity_at_index(parent.index()).unwrap();
+        self.hierarchy.set_parent(child, parent)
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        if self.entities.is_foreign(entity) {
+            return None;
+        }
+        self.hierarchy.parent(entity)
+    }
+
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        if self.entities.is_foreign(entity) {
+            return &[];
+        }
+        self.hierarchy.children(entity)
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    // `Some` only if the entity is alive in this world, foreign and despawned entities are just `None`.
+    // Never panics, not even for a foreign entity under `StrictMode::Panic`
+    pub fn get_weak(&self, entity: Entity) -> Option<Entity> {
+        match self.entities.is_alive_quiet(entity) {
+            true => Some(entity),
+            false => None,
+        }
+    }
+
+    // Despawned entities are only recorded for `drain_despawned` once this is turned on, after that
+    // they're held on to until they're drained so keep draining them for as long as it's on
+    pub fn set_record_despawns(&mut self, enabled: bool) {
+        self.entities.set_record_despawns(enabled);
+    }
+
+    // Everything despawned since the last call, always empty unless `set_record_despawns` is on
+    pub fn drain_despawned(&mut self) -> std::vec::Drain<'_, Entity> {
+        self.entities.drain_despawned()
+    }
+
+    // Returns the component that was already on the entity if there was one
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        if self.is_alive(entity) == false {
+            panic!("Attempted to insert a component on a dead entity");
+        }
+        // Store this world's own handle rather than e.g. one loaded with `from_bits`, so that `query` hands
+        // back handles that other worlds will turn away
+        let entity = self.entities.entity_at_index(entity.index()).unwrap();
+
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(ComponentStorage::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<ComponentStorage<T>>()
+            .unwrap()
+            .insert(entity, component)
+    }
+
+    // Storages only look at the index and generation so anything from another world has to be turned
+    // away here, otherwise it would find whatever this world has with the same index and generation
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if self.entities.is_foreign(entity) {
+            return None;
+        }
+        self.storage::<T>()?.get(entity)
+    }
+
+    // Handing out a `&mut` marks the component as changed, see `query_changed`
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if self.entities.is_foreign(entity) {
+            return None;
+        }
+        self.storage_mut::<T>()?.get_mut(entity)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if self.entities.is_foreign(entity) {
+            return None;
+        }
+        self.storage_mut::<T>()?.remove(entity)
+    }
+
+    // Removes every T in the world, the entities themselves are left alive
+    pub fn clear_component<T: 'static>(&mut self) {
+        if let Some(storage) = self.storage_mut::<T>() {
+            storage.clear();
+        }
+    }
+
+    // Checks whether the entity has a T without borrowing the component
+    pub fn contains<T: 'static>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    // Walks the storage's dense array directly rather than looking up every alive entity
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter())
+            .filter(move |&(entity, _)| self.is_alive(entity))
+    }
+
+    // The version of the most recent change to any T component, pass it to `query_changed`
+    // later on to get every T that has been inserted or mutably borrowed after this point
+    pub fn change_version<T: 'static>(&self) -> u64 {
+        match self.storage::<T>() {
+            Some(storage) => storage.version(),
+            None => 0,
+        }
+    }
+
+    pub fn changed_since<T: 'static>(&self, entity: Entity, version: u64) -> bool {
+        match self.storage::<T>() {
+            Some(storage) if self.entities.is_foreign(entity) == false => {
+                storage.changed_since(entity, version)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn query_changed<T: 'static>(&self, since: u64) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(move |storage| storage.iter_changed(since))
+            .filter(move |&(entity, _)| self.is_alive(entity))
+    }
+
+    // Entities that have both an A and a B component
+    pub fn query2<A: 'static, B: 'static>(
+        &self,
+    ) -> Box<dyn Iterator<Item = (Entity, &A, &B)> + '_> {
+        let (a_storage, b_storage) = match (self.storage::<A>(), self.storage::<B>()) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Box::new(std::iter::empty()),
+        };
+
+        // Walk whichever storage is smaller and look the entity up in the other one, `get` checks
+        // the whole entity so a component left over from an old generation won't get joined on
+        let joined: Box<dyn Iterator<Item = (Entity, &A, &B)>> =
+            match a_storage.len() <= b_storage.len() {
+                true => Box::new(
+                    a_storage
+                        .iter()
+                        .filter_map(move |(e, a)| Some((e, a, b_storage.get(e)?))),
+                ),
+                false => Box::new(
+                    b_storage
+                        .iter()
+                        .filter_map(move |(e, b)| Some((e, a_storage.get(e)?, b))),
+                ),
+            };
+        Box::new(joined.filter(move |&(entity, _, _)| self.is_alive(entity)))
+    }
+
+    // Needs the `rayon` feature. Every T in the world, split up across rayon's thread pool. Each piece
+    // bumps its components' change versions just like `get_mut` so `query_changed` still sees them
+    #[cfg(feature = "rayon")]
+    pub fn par_query_mut<T: Send + 'static>(
+        &mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = &mut T> {
+        use rayon::prelude::*;
+
+        let storage = self.storages.get_mut(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .as_any_mut()
+                .downcast_mut::<ComponentStorage<T>>()
+                .unwrap()
+        });
+
+        let entities = &self.entities;
+        storage
+            .into_par_iter()
+            .flat_map(|storage| storage.par_iter_mut())
+            .filter(move |(entity, _)| entities.is_alive(*entity))
+            .map(|(_, component)| component)
+    }
+
+    // A and B live in different storages so handing out a `&mut` to both at once is fine, asking for
+    // the same type twice would mean two `&mut` to the same component so we panic on that
+    pub fn query2_mut<A: 'static, B: 'static>(
+        &mut self,
+    ) -> impl Iterator<Item = (Entity, &mut A, &mut B)> {
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            panic!("Attempted to mutably query the same component type twice");
+        }
+
+        let [a_storage, b_storage] = self
+            .storages
+            .get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()]);
+        let a_storage = a_storage.map(|storage| {
+            storage
+                .as_any_mut()
+                .downcast_mut::<ComponentStorage<A>>()
+                .unwrap()
+        });
+        let b_storage = b_storage.map(|storage| {
+            storage
+                .as_any_mut()
+                .downcast_mut::<ComponentStorage<B>>()
+                .unwrap()
+        });
+
+        let entities = &self.entities;
+        a_storage
+            .zip(b_storage)
+            .into_iter()
+            .flat_map(|(a, b)| a.join_mut(b))
+            .filter(move |(entity, _, _)| entities.is_alive(*entity))
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&ComponentStorage<T>> {
+        Some(
+            self.storages
+                .get(&TypeId::of::<T>())?
+                .as_any()
+                .downcast_ref::<ComponentStorage<T>>()
+                .unwrap(),
+        )
+    }
+
+    fn storage_mut<T: 'static>(&mut self) -> Option<&mut ComponentStorage<T>> {
+        Some(
+            self.storages
+                .get_mut(&TypeId::of::<T>())?
+                .as_any_mut()
+                .downcast_mut::<ComponentStorage<T>>()
+                .unwrap(),
+        )
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl EntityBuilder<'_> {
+    pub fn with<T: 'static>(self, component: T) -> Self {
+        self.world.insert(self.entity, component);
+        self
+    }
+
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+// An entity that has to be upgraded through a world before it can be used, like `Weak::upgrade` for `Rc`.
+// Handy for passing entities around where you want to be forced to deal with them having been despawned
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct WeakEntity(Entity);
+
+impl WeakEntity {
+    pub fn new(entity: Entity) -> Self {
+        WeakEntity(entity)
+    }
+
+    pub fn upgrade(&self, world: &World) -> Option<Entity> {
+        world.get_weak(self.0)
+    }
+}
+
+impl From<Entity> for WeakEntity {
+    fn from(entity: Entity) -> Self {
+        WeakEntity::new(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weak_entity() {
+        let mut world = World::new();
+        let e1 = world.spawn();
+        let e2 = world.spawn();
+        world.despawn(e2);
+        let foreign = World::new().spawn();
+
+        assert_eq!(world.get_weak(e1), Some(e1));
+        assert_eq!(world.get_weak(e2), None);
+        assert_eq!(world.get_weak(foreign), None);
+        assert_eq!(world.get_weak(Entity::from_bits(7 << 32 | 100)), None);
+
+        let weak = WeakEntity::from(e1);
+        assert_eq!(weak.upgrade(&world), Some(e1));
+        world.despawn(e1);
+        assert_eq!(weak.upgrade(&world), None);
+    }
+
+    #[test]
+    fn foreign_entity_components() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((10_u32,));
+        let e2 = world.spawn();
+        world.set_parent(e2, e1).unwrap();
+
+        // Same index and generation as e1 but from another world, every lookup has to miss
+        let foreign = World::new().spawn();
+        assert_eq!(foreign.to_bits(), e1.to_bits());
+        assert_eq!(world.get::<u32>(foreign), None);
+        assert!(world.get_mut::<u32>(foreign).is_none());
+        assert!(world.contains::<u32>(foreign) == false);
+        assert!(world.changed_since::<u32>(foreign, 0) == false);
+        assert_eq!(world.remove::<u32>(foreign), None);
+        assert_eq!(world.get::<u32>(e1), Some(&10));
+        assert!(world.children(foreign).is_empty());
+        assert_eq!(world.parent(e2), Some(e1));
+    }
+
+    #[test]
+    fn loaded_entity() {
+        let mut world = World::new();
+        let parent = world.spawn_bundle((10_u32,));
+        let child = world.spawn_bundle((20_u32,));
+        world.set_parent(child, parent).unwrap();
+
+        // A handle loaded from a save isn't tied to a world, it has to reach the same components and
+        // children as the handle it was saved from
+        let loaded = Entity::from_bits(parent.to_bits());
+        assert_eq!(loaded, parent);
+        assert_eq!(world.get::<u32>(loaded), Some(&10));
+        assert_eq!(world.children(loaded), &[child]);
+
+        assert!(world.despawn(loaded));
+        assert!(world.is_alive(child) == false);
+        assert_eq!(world.storage::<u32>().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn weak_entity_strict() {
+        let mut world = World::new();
+        world.set_strict_mode(StrictMode::Panic);
+        let e1 = world.spawn();
+        let foreign = World::new().spawn();
+
+        assert_eq!(world.get_weak(foreign), None);
+        assert_eq!(WeakEntity::from(foreign).upgrade(&world), None);
+        assert_eq!(world.get_weak(e1), Some(e1));
+    }
+
+    #[test]
+    fn insert_get() {
+        let mut world = World::new();
+        let e1 = world.spawn();
+        let e2 = world.spawn();
+
+        assert_eq!(world.insert(e1, 10_u32), None);
+        assert_eq!(world.insert(e1, true), None);
+        assert_eq!(world.insert(e1, 12_u32), Some(10));
+
+        assert_eq!(world.get::<u32>(e1), Some(&12));
+        assert_eq!(world.get::<bool>(e1), Some(&true));
+        assert_eq!(world.get::<u32>(e2), None);
+        assert_eq!(world.get::<u64>(e1), None);
+    }
+
+    #[test]
+    fn despawn_cascades_to_children() {
+        let mut world = World::new();
+        let root = world.spawn();
+        let (a, b) = (world.spawn(), world.spawn());
+        let (a1, a2, b1) = (world.spawn(), world.spawn(), world.spawn());
+        let unrelated = world.spawn_bundle((Position(1),));
+        world.set_parent(a, root).unwrap();
+        world.set_parent(b, root).unwrap();
+        world.set_parent(a1, a).unwrap();
+        world.set_parent(a2, a).unwrap();
+        world.set_parent(b1, b).unwrap();
+        world.insert(a2, Position(2));
+        assert_eq!(world.children(root), &[a, b]);
+
+        // A child that goes first just drops out of the tree
+        assert!(world.despawn(a1));
+        assert_eq!(world.children(a), &[a2]);
+
+        assert!(world.despawn(root));
+        for &e in [root, a, b, a1, a2, b1].iter() {
+            assert!(world.is_alive(e) == false);
+            assert!(world.children(e).is_empty());
+            assert_eq!(world.parent(e), None);
+        }
+        assert_eq!(world.get::<Position>(a2), None);
+        assert!(world.is_alive(unrelated));
+        assert!(world.despawn(root) == false);
+    }
+
+    #[test]
+    fn despawn_removes_components() {
+        let mut world = World::new();
+        let e1 = world.spawn();
+        let e2 = world.spawn();
+        world.insert(e1, 10_u32);
+        world.insert(e1, true);
+        world.insert(e2, 20_u32);
+
+        assert!(world.despawn(e1));
+        assert!(world.is_alive(e1) == false);
+        assert_eq!(world.get::<u32>(e1), None);
+        assert_eq!(world.get::<bool>(e1), None);
+        assert_eq!(world.get::<u32>(e2), Some(&20));
+    }
+
+    #[test]
+    fn drain_despawned() {
+        let mut world = World::new();
+        let parent = world.spawn();
+        let child = world.spawn();
+        world.set_parent(child, parent).unwrap();
+        let unrecorded = world.spawn();
+        world.despawn(unrecorded);
+        assert_eq!(world.drain_despawned().count(), 0);
+
+        world.set_record_despawns(true);
+        world.despawn(parent);
+        let mut drained: Vec<_> = world.drain_despawned().collect();
+        drained.sort();
+        assert_eq!(drained, vec![parent, child]);
+    }
+
+    #[test]
+    fn double_despawn() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((Position(1), Velocity(2)));
+        let e2 = world.spawn_bundle((Position(3), Velocity(4)));
+
+        assert!(world.despawn(e1));
+        assert!(world.despawn(e1) == false);
+        // The second despawn doesn't go anywhere near the storages so e2's components are untouched
+        assert_eq!(world.storage::<Position>().unwrap().len(), 1);
+        assert_eq!(world.storage::<Velocity>().unwrap().len(), 1);
+        assert_eq!(world.get::<Position>(e2), Some(&Position(3)));
+        assert_eq!(world.get::<Velocity>(e2), Some(&Velocity(4)));
+    }
+
+    #[test]
+    #[should_panic(expected = "already dead")]
+    fn double_despawn_strict() {
+        let mut world = World::new();
+        world.set_strict_mode(StrictMode::Panic);
+        let e1 = world.spawn_bundle((Position(1), Velocity(2)));
+        world.despawn(e1);
+        world.despawn(e1);
+    }
+
+    #[test]
+    fn clear_component() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..5)
+            .map(|i| world.spawn_bundle((Position(i), Velocity(i * 2))))
+            .collect();
+
+        world.clear_component::<Position>();
+        assert_eq!(world.query::<Position>().count(), 0);
+        for (i, &e) in entities.iter().enumerate() {
+            assert!(world.is_alive(e));
+            assert_eq!(world.get::<Velocity>(e), Some(&Velocity(i as i32 * 2)));
+        }
+
+        // Clearing something that was never inserted is fine too
+        world.clear_component::<bool>();
+    }
+
+    #[test]
+    fn respawn_has_no_leftover_components() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((Position(1), Velocity(2)));
+        world.despawn(e1);
+
+        let e2 = world.spawn();
+        assert_eq!(e1.index(), e2.index());
+        assert_eq!(world.get::<Position>(e2), None);
+        assert_eq!(world.get::<Velocity>(e2), None);
+        assert_eq!(world.query::<Position>().count(), 0);
+        assert_eq!(world.storage::<Position>().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn contains() {
+        let mut world = World::new();
+        let e1 = world.spawn_bundle((Position(1), Velocity(2)));
+        assert!(world.contains::<Position>(e1));
+        assert!(world.contains::<Velocity>(e1));
+        assert!(world.contains::<bool>(e1) == false);
+
+        assert_eq!(world.remove::<Position>(e1), Some(Position(1)));
+        assert!(world.contains::<Position>(e1) == false);
+        assert!(world.contains::<Velocity>(e1));
+
+        world.despawn(e1);
+        assert!(world.contains::<Velocity>(e1) == false);
+    }
+
+    #[test]
+    fn query_changed() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..4).map(|i| world.spawn_bundle((Position(i),))).collect();
+        let since = world.change_version::<Position>();
+        assert_eq!(world.query_changed::<Position>(since).count(), 0);
+
+        world.get_mut::<Position>(entities[0]).unwrap().0 += 5;
+        world.get_mut::<Position>(entities[2]).unwrap().0 += 5;
+
+        let changed: Vec<_> = world.query_changed::<Position>(since).collect();
+        assert_eq!(
+            changed,
+            vec![(entities[0], &Position(5)), (entities[2], &Position(7))]
+        );
+        assert!(world.changed_since::<Position>(entities[2], since));
+        assert!(world.changed_since::<Position>(entities[1], since) == false);
+        assert_eq!(world.query_changed::<Velocity>(0).count(), 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_query_mut() {
+        use rayon::prelude::*;
+
+        let mut world = World::new();
+        let entities: Vec<_> = (0..1000)
+            .map(|i| world.spawn_bundle((Position(i),)))
+            .collect();
+        world.spawn_bundle((Velocity(3),));
+        let since = world.change_version::<Position>();
+
+        // One thread is enough to check the results, splitting is rayon's problem. `World` itself isn't
+        // `Send` (components don't have to be) so the iterator gets made out here and moved into the pool
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let positions = world.par_query_mut::<Position>();
+        pool.install(move || positions.for_each(|position| position.0 *= 2));
+
+        for (i, &entity) in entities.iter().enumerate() {
+            assert_eq!(world.get::<Position>(entity), Some(&Position(i as i32 * 2)));
+        }
+        assert_eq!(world.query_changed::<Position>(since).count(), 1000);
+        assert_eq!(world.par_query_mut::<u64>().count(), 0);
+    }
+
+    #[test]
+    fn query() {
+        let mut world = World::new();
+        let e1 = world.spawn();
+        let e2 = world.spawn();
+        let e3 = world.spawn();
+        let e4 = world.spawn();
+        world.insert(e1, 1_u32);
+        world.insert(e2, 2_u32);
+        world.insert(e3, 3_u32);
+        world.insert(e4, true);
+
+        world.despawn(e2);
+        let results: Vec<_> = world.query::<u32>().collect();
+        assert_eq!(results, vec![(e1, &1), (e3, &3)]);
+        assert_eq!(world.query::<u64>().count(), 0);
+    }
+
+    #[test]
+    fn query2() {
+        let mut world = World::new();
+        let a_only = world.spawn();
+        let b_only = world.spawn();
+        let both_1 = world.spawn();
+        let both_2 = world.spawn();
+        world.insert(a_only, 1_u32);
+        world.insert(b_only, 'b');
+        world.insert(both_1, 2_u32);
+        world.insert(both_1, 'x');
+        world.insert(both_2, 'y');
+        world.insert(both_2, 3_u32);
+
+        let mut results: Vec<_> = world.query2::<u32, char>().collect();
+        results.sort();
+        assert_eq!(results, vec![(both_1, &2, &'x'), (both_2, &3, &'y')]);
+
+        // Same thing when the other storage is the smaller one
+        for i in 0..3 {
+            let e = world.spawn();
+            world.insert(e, 10_u32 + i);
+        }
+        let mut results: Vec<_> = world.query2::<u32, char>().collect();
+        results.sort();
+        assert_eq!(results, vec![(both_1, &2, &'x'), (both_2, &3, &'y')]);
+
+        assert_eq!(world.query2::<u32, bool>().count(), 0);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+
+    #[test]
+    fn query2_mut() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..4).map(|_| world.spawn()).collect();
+        for (i, &e) in entities.iter().enumerate() {
+            world.insert(e, Position(i as i32));
+        }
+        world.insert(entities[1], Velocity(10));
+        world.insert(entities[3], Velocity(-5));
+        world.insert(entities[2], Velocity(7));
+        world.despawn(entities[2]);
+
+        for (_, position, velocity) in world.query2_mut::<Position, Velocity>() {
+            position.0 += velocity.0;
+        }
+
+        assert_eq!(world.get(entities[0]), Some(&Position(0)));
+        assert_eq!(world.get(entities[1]), Some(&Position(11)));
+        assert_eq!(world.get(entities[3]), Some(&Position(-2)));
+        assert_eq!(world.get(entities[3]), Some(&Velocity(-5)));
+        assert_eq!(world.query2_mut::<Position, bool>().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn query2_mut_same_type() {
+        let mut world = World::new();
+        world.query2_mut::<Position, Position>().count();
+    }
+
+    #[test]
+    fn entity_builder() {
+        let mut world = World::new();
+        let e1 = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Velocity(2))
+            .build();
+
+        assert!(world.is_alive(e1));
+        assert_eq!(world.get(e1), Some(&Position(1)));
+        assert_eq!(world.get(e1), Some(&Velocity(2)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_dead() {
+        let mut world = World::new();
+        let e1 = world.spawn();
+        world.despawn(e1);
+        world.insert(e1, 10_u32);
+    }
+}
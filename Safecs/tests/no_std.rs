@@ -0,0 +1,19 @@
+// Compile check for the `std`-less build, the test itself is `no_std` so it can only touch what's left of
+// safecs without `std`. Run it with `cargo test --no-default-features --test no_std`
+#![no_std]
+
+use safecs::{CompactEntity, Entity, EntityMap};
+
+#[test]
+fn allocator_types_without_std() {
+    let e = Entity::from_raw_parts(3, 2);
+    assert_eq!(Entity::from_bits(e.to_bits()), e);
+
+    let mut map = EntityMap::new();
+    map.insert(e, 23);
+    assert_eq!(map.get(e), Some(&23));
+    assert_eq!(map.get(Entity::from_raw_parts(3, 1)), None);
+
+    let compact = CompactEntity::from_bits(e.to_bits() as u32);
+    assert_eq!(compact.index(), 3);
+}